@@ -0,0 +1,26 @@
+// Benchmark for the resolution of SegmentTemplate URL templates, which happens once per media
+// segment. Manifests with a long SegmentTimeline can contain tens of thousands of segments.
+//
+// Run with "cargo bench --bench url_template".
+
+use std::collections::HashMap;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dash_mpd::resolve_url_template;
+
+
+fn resolve_timeline(c: &mut Criterion) {
+    let params = HashMap::from([("RepresentationID", String::from("video-1080p")),
+                                ("Bandwidth", String::from("5000000"))]);
+    let template = resolve_url_template("$RepresentationID$/$Bandwidth$/t$Time$-$Number%06d$.m4s", &params);
+    c.bench_function("resolve 10k-segment timeline", |b| b.iter(|| {
+        let mut segment_params = HashMap::with_capacity(2);
+        for number in 1..=10_000u64 {
+            segment_params.insert("Time", (number * 2000).to_string());
+            segment_params.insert("Number", number.to_string());
+            black_box(resolve_url_template(black_box(&template), &segment_params));
+        }
+    }));
+}
+
+criterion_group!(benches, resolve_timeline);
+criterion_main!(benches);
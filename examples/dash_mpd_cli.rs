@@ -0,0 +1,196 @@
+// dash_mpd_cli.rs -- a command-line downloader exposing the options of DashDownloader
+//
+// Run with `cargo run --example dash_mpd_cli -- --quality=best -o media.mp4 <URL>`
+//
+// The tests in tests/mock_server.rs also run this example against manifests served by a local
+// HTTP server, to exercise the download path end to end.
+
+use std::process;
+use std::sync::Arc;
+use env_logger::Env;
+use clap::{Arg, ArgAction};
+use indicatif::{ProgressBar, ProgressStyle};
+use colored::*;
+use dash_mpd::fetch::DashDownloader;
+use dash_mpd::fetch::ProgressObserver;
+
+
+struct DownloadProgressBar {
+    bar: ProgressBar,
+}
+
+impl DownloadProgressBar {
+    pub fn new() -> Self {
+        let b = ProgressBar::new(100)
+            .with_style(ProgressStyle::default_bar()
+                        .template("[{elapsed}] {bar:50.cyan/blue} {wide_msg}")
+                        .expect("building progress bar")
+                        .progress_chars("#>-"));
+        Self { bar: b }
+    }
+}
+
+impl ProgressObserver for DownloadProgressBar {
+    fn update(&self, percent: u32, message: &str) {
+        if percent <= 100 {
+            self.bar.set_position(percent.into());
+            self.bar.set_message(message.to_string());
+        }
+        if percent == 100 {
+            self.bar.finish_with_message("Done");
+        }
+    }
+}
+
+fn flag(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .long(name)
+        .action(ArgAction::SetTrue)
+        .help(help)
+}
+
+fn main () {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info,reqwest=warn")).init();
+    let matches = clap::Command::new("dash_mpd_cli")
+        .about("Download content from a DASH streaming media manifest")
+        .arg(Arg::new("quality")
+             .long("quality")
+             .num_args(1)
+             .value_parser(["best", "worst"])
+             .help("Prefer the best or the worst quality representation (default: worst)"))
+        .arg(Arg::new("prefer-language")
+             .long("prefer-language")
+             .num_args(1)
+             .value_name("LANG")
+             .help("Preferred language for the audio stream (eg. \"fr\" or \"en-AU\")"))
+        .arg(Arg::new("audio-channels")
+             .long("audio-channels")
+             .num_args(1)
+             .value_parser(clap::value_parser!(u8))
+             .help("Preferred number of audio channels (eg. 2 for stereo, 6 for 5.1)"))
+        .arg(Arg::new("exclude-codecs")
+             .long("exclude-codecs")
+             .num_args(1)
+             .value_delimiter(',')
+             .value_name("CODECS")
+             .help("Don't download streams using these codecs (comma-separated, eg. \"ec-3,ac-4\")"))
+        .arg(flag("video-only", "Only download the video stream"))
+        .arg(flag("audio-only", "Only download the audio stream"))
+        .arg(flag("subtitles", "Download subtitles, if available"))
+        .arg(flag("keep-video", "Don't delete the file containing the video stream after muxing"))
+        .arg(flag("keep-audio", "Don't delete the file containing the audio stream after muxing"))
+        .arg(flag("no-content-type-checks", "Don't check the content-type of media segments"))
+        .arg(flag("no-xattr", "Don't record metainformation as extended attributes in the output file"))
+        .arg(flag("no-progress", "Don't display a progress bar"))
+        .arg(flag("dry-run", "Check that the media segments are reachable, without downloading them"))
+        .arg(Arg::new("sleep-requests")
+             .long("sleep-requests")
+             .num_args(1)
+             .value_parser(clap::value_parser!(u8))
+             .value_name("SECONDS")
+             .help("Number of seconds to sleep between network requests"))
+        .arg(Arg::new("max-error-count")
+             .long("max-error-count")
+             .num_args(1)
+             .value_parser(clap::value_parser!(usize))
+             .help("Maximum number of segment download errors tolerated per stream"))
+        .arg(Arg::new("verbose")
+             .short('v')
+             .long("verbose")
+             .action(ArgAction::Count)
+             .help("Level of verbosity (can be used several times)"))
+        .arg(Arg::new("ffmpeg")
+             .long("ffmpeg")
+             .num_args(1)
+             .value_name("PATH")
+             .help("Path to the ffmpeg binary"))
+        .arg(Arg::new("vlc")
+             .long("vlc")
+             .num_args(1)
+             .value_name("PATH")
+             .help("Path to the VLC binary"))
+        .arg(Arg::new("mkvmerge")
+             .long("mkvmerge")
+             .num_args(1)
+             .value_name("PATH")
+             .help("Path to the mkvmerge binary"))
+        .arg(Arg::new("output")
+             .short('o')
+             .long("output")
+             .num_args(1)
+             .value_name("PATH")
+             .help("Save media content to this file (default: derived from the manifest URL)"))
+        .arg(Arg::new("url")
+             .num_args(1)
+             .value_name("MPD-URL")
+             .help("URL of the MPD manifest")
+             .required(true)
+             .index(1))
+        .get_matches();
+    let url = matches.get_one::<String>("url").unwrap();
+    let mut dl = DashDownloader::new(url)
+        .expect("parsing MPD URL")
+        .verbosity(matches.get_count("verbose"))
+        .record_metainformation(!matches.get_flag("no-xattr"))
+        .dry_run(matches.get_flag("dry-run"));
+    if !matches.get_flag("no-progress") {
+        dl = dl.add_progress_observer(Arc::new(DownloadProgressBar::new()));
+    }
+    if matches.get_one::<String>("quality").map(String::as_str) == Some("best") {
+        dl = dl.best_quality();
+    }
+    if let Some(lang) = matches.get_one::<String>("prefer-language") {
+        dl = dl.prefer_language(lang.to_string());
+    }
+    if let Some(channels) = matches.get_one::<u8>("audio-channels") {
+        dl = dl.prefer_audio_channels(*channels);
+    }
+    if let Some(codecs) = matches.get_many::<String>("exclude-codecs") {
+        let codecs: Vec<&str> = codecs.map(String::as_str).collect();
+        dl = dl.exclude_codecs(&codecs);
+    }
+    if matches.get_flag("video-only") {
+        dl = dl.video_only();
+    }
+    if matches.get_flag("audio-only") {
+        dl = dl.audio_only();
+    }
+    if matches.get_flag("subtitles") {
+        dl = dl.fetch_subtitles();
+    }
+    if matches.get_flag("keep-video") {
+        dl = dl.keep_video();
+    }
+    if matches.get_flag("keep-audio") {
+        dl = dl.keep_audio();
+    }
+    if matches.get_flag("no-content-type-checks") {
+        dl = dl.without_content_type_checks();
+    }
+    if let Some(seconds) = matches.get_one::<u8>("sleep-requests") {
+        dl = dl.sleep_between_requests(*seconds);
+    }
+    if let Some(count) = matches.get_one::<usize>("max-error-count") {
+        dl = dl.max_error_count_per_stream(*count);
+    }
+    if let Some(path) = matches.get_one::<String>("ffmpeg") {
+        dl = dl.with_ffmpeg(path);
+    }
+    if let Some(path) = matches.get_one::<String>("vlc") {
+        dl = dl.with_vlc(path);
+    }
+    if let Some(path) = matches.get_one::<String>("mkvmerge") {
+        dl = dl.with_mkvmerge(path);
+    }
+    let result = match matches.get_one::<String>("output") {
+        Some(out) => dl.download_to(out),
+        None => dl.download(),
+    };
+    match result {
+        Ok(path) => println!("Downloaded to {}", path.display()),
+        Err(e) => {
+            eprintln!("{}: {e}", "Download failed".red());
+            process::exit(2);
+        },
+    }
+}
@@ -13,6 +13,7 @@ fn main () {
     env_logger::Builder::from_env(Env::default().default_filter_or("info,reqwest=warn")).init();
     let url = "http://rdmedia.bbc.co.uk/dash/ondemand/testcard/1/client_manifest-ctv-events.mpd";
     let ddl = DashDownloader::new(url)
+        .expect("parsing MPD URL")
         .worst_quality()
         .verbosity(2);
     #[cfg(target_os = "windows")]
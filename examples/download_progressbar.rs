@@ -57,6 +57,7 @@ fn main () {
         .get_matches();
     let url = matches.get_one::<String>("url").unwrap();
     let mut dl = DashDownloader::new(url)
+        .expect("parsing MPD URL")
         .record_metainformation(false)
         .add_progress_observer(Arc::new(DownloadProgressBar::new()));
     if let Some(q) = matches.get_one::<String>("quality") {
@@ -45,6 +45,7 @@ fn main () {
     let url = "https://cloudflarestream.com/31c9291ab41fac05471db4e73aa11717/manifest/video.mpd";
     let out = PathBuf::from(env::temp_dir()).join("cloudflarestream.mkv");
     match DashDownloader::new(url)
+        .expect("parsing MPD URL")
         .with_http_client(client)
         .worst_quality()
         .download_to(out) {
@@ -0,0 +1,326 @@
+//! Builders for constructing an MPD manifest programmatically.
+//!
+//! The structs of the manifest model have a large number of public fields, most of which are
+//! optional. These builders provide fluent methods for the most frequently needed fields, which is
+//! more convenient than a struct literal when generating a manifest or writing unit tests. Fields
+//! that aren't covered by a builder method can be set on the struct returned by `build`.
+//!
+//! ```
+//! use std::time::Duration;
+//! use dash_mpd::builder::{MpdBuilder, PeriodBuilder, AdaptationSetBuilder, RepresentationBuilder};
+//!
+//! let mpd = MpdBuilder::new()
+//!     .media_presentation_duration(Duration::from_secs(60))
+//!     .period(PeriodBuilder::new()
+//!             .id("1")
+//!             .adaptation(AdaptationSetBuilder::new()
+//!                         .content_type("video")
+//!                         .mime_type("video/mp4")
+//!                         .representation(RepresentationBuilder::new("v1")
+//!                                         .bandwidth(500_000)
+//!                                         .resolution(640, 360)
+//!                                         .base_url("video.mp4")
+//!                                         .build())
+//!                         .build())
+//!             .build())
+//!     .build();
+//! assert_eq!(mpd.periods[0].adaptations[0].representations[0].width, Some(640));
+//! ```
+
+
+use std::time::Duration;
+use crate::{MPD, Period, AdaptationSet, Representation, BaseURL, Location, SegmentTemplate, SegmentBase, SegmentList};
+use crate::AudioChannelConfiguration;
+
+
+fn base_url(url: &str) -> BaseURL {
+    BaseURL { base: String::from(url), ..Default::default() }
+}
+
+
+/// A builder for a `Representation`.
+#[derive(Debug, Default, Clone)]
+pub struct RepresentationBuilder {
+    representation: Representation,
+}
+
+impl RepresentationBuilder {
+    /// A builder for a Representation with the specified `@id`.
+    pub fn new(id: &str) -> RepresentationBuilder {
+        RepresentationBuilder {
+            representation: Representation { id: Some(String::from(id)), ..Default::default() },
+        }
+    }
+
+    pub fn mime_type(mut self, mime_type: &str) -> RepresentationBuilder {
+        self.representation.mimeType = Some(String::from(mime_type));
+        self
+    }
+
+    /// An RFC6381 codecs string, such as "avc1.4D401E".
+    pub fn codecs(mut self, codecs: &str) -> RepresentationBuilder {
+        self.representation.codecs = Some(String::from(codecs));
+        self
+    }
+
+    pub fn bandwidth(mut self, bandwidth: u64) -> RepresentationBuilder {
+        self.representation.bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// The `@width` and `@height` of the video.
+    pub fn resolution(mut self, width: u64, height: u64) -> RepresentationBuilder {
+        self.representation.width = Some(width);
+        self.representation.height = Some(height);
+        self
+    }
+
+    /// The frame rate, such as "25" or "30000/1001".
+    pub fn frame_rate(mut self, frame_rate: &str) -> RepresentationBuilder {
+        self.representation.frameRate = Some(String::from(frame_rate));
+        self
+    }
+
+    pub fn audio_sampling_rate(mut self, rate: u64) -> RepresentationBuilder {
+        self.representation.audioSamplingRate = Some(rate);
+        self
+    }
+
+    pub fn audio_channel_configuration(mut self, scheme_id_uri: &str, value: &str) -> RepresentationBuilder {
+        self.representation.AudioChannelConfiguration = Some(AudioChannelConfiguration {
+            schemeIdUri: Some(String::from(scheme_id_uri)),
+            value: Some(String::from(value)),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn selection_priority(mut self, priority: u64) -> RepresentationBuilder {
+        self.representation.selectionPriority = Some(priority);
+        self
+    }
+
+    /// Add a BaseURL (several BaseURLs can be added, for redundancy).
+    pub fn base_url(mut self, url: &str) -> RepresentationBuilder {
+        self.representation.BaseURL.push(base_url(url));
+        self
+    }
+
+    pub fn segment_template(mut self, template: SegmentTemplate) -> RepresentationBuilder {
+        self.representation.SegmentTemplate = Some(template);
+        self
+    }
+
+    pub fn segment_base(mut self, segment_base: SegmentBase) -> RepresentationBuilder {
+        self.representation.SegmentBase = Some(segment_base);
+        self
+    }
+
+    pub fn segment_list(mut self, segment_list: SegmentList) -> RepresentationBuilder {
+        self.representation.SegmentList = Some(segment_list);
+        self
+    }
+
+    pub fn build(self) -> Representation {
+        self.representation
+    }
+}
+
+
+/// A builder for an `AdaptationSet`.
+#[derive(Debug, Default, Clone)]
+pub struct AdaptationSetBuilder {
+    adaptation: AdaptationSet,
+}
+
+impl AdaptationSetBuilder {
+    pub fn new() -> AdaptationSetBuilder {
+        AdaptationSetBuilder::default()
+    }
+
+    pub fn id(mut self, id: i64) -> AdaptationSetBuilder {
+        self.adaptation.id = Some(id);
+        self
+    }
+
+    /// The content type, such as "audio", "video" or "text".
+    pub fn content_type(mut self, content_type: &str) -> AdaptationSetBuilder {
+        self.adaptation.contentType = Some(String::from(content_type));
+        self
+    }
+
+    pub fn mime_type(mut self, mime_type: &str) -> AdaptationSetBuilder {
+        self.adaptation.mimeType = Some(String::from(mime_type));
+        self
+    }
+
+    /// An RFC6381 codecs string, shared by the Representations.
+    pub fn codecs(mut self, codecs: &str) -> AdaptationSetBuilder {
+        self.adaptation.codecs = Some(String::from(codecs));
+        self
+    }
+
+    /// The content language, in RFC 5646 format.
+    pub fn lang(mut self, lang: &str) -> AdaptationSetBuilder {
+        self.adaptation.lang = Some(String::from(lang));
+        self
+    }
+
+    pub fn group(mut self, group: i64) -> AdaptationSetBuilder {
+        self.adaptation.group = Some(group);
+        self
+    }
+
+    pub fn selection_priority(mut self, priority: u64) -> AdaptationSetBuilder {
+        self.adaptation.selectionPriority = Some(priority);
+        self
+    }
+
+    /// The `@maxWidth` and `@maxHeight` of the Representations.
+    pub fn max_resolution(mut self, width: u64, height: u64) -> AdaptationSetBuilder {
+        self.adaptation.maxWidth = Some(width);
+        self.adaptation.maxHeight = Some(height);
+        self
+    }
+
+    /// Add a BaseURL (several BaseURLs can be added, for redundancy).
+    pub fn base_url(mut self, url: &str) -> AdaptationSetBuilder {
+        self.adaptation.BaseURL.push(base_url(url));
+        self
+    }
+
+    pub fn segment_template(mut self, template: SegmentTemplate) -> AdaptationSetBuilder {
+        self.adaptation.SegmentTemplate = Some(template);
+        self
+    }
+
+    pub fn segment_list(mut self, segment_list: SegmentList) -> AdaptationSetBuilder {
+        self.adaptation.SegmentList = Some(segment_list);
+        self
+    }
+
+    pub fn representation(mut self, representation: Representation) -> AdaptationSetBuilder {
+        self.adaptation.representations.push(representation);
+        self
+    }
+
+    pub fn build(self) -> AdaptationSet {
+        self.adaptation
+    }
+}
+
+
+/// A builder for a `Period`.
+#[derive(Debug, Default, Clone)]
+pub struct PeriodBuilder {
+    period: Period,
+}
+
+impl PeriodBuilder {
+    pub fn new() -> PeriodBuilder {
+        PeriodBuilder::default()
+    }
+
+    pub fn id(mut self, id: &str) -> PeriodBuilder {
+        self.period.id = Some(String::from(id));
+        self
+    }
+
+    /// The `@start` attribute, an xs:duration such as "PT0S".
+    pub fn start(mut self, start: &str) -> PeriodBuilder {
+        self.period.start = Some(String::from(start));
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> PeriodBuilder {
+        self.period.duration = Some(duration);
+        self
+    }
+
+    /// Add a BaseURL (several BaseURLs can be added, for redundancy).
+    pub fn base_url(mut self, url: &str) -> PeriodBuilder {
+        self.period.BaseURL.push(base_url(url));
+        self
+    }
+
+    pub fn segment_template(mut self, template: SegmentTemplate) -> PeriodBuilder {
+        self.period.SegmentTemplate = Some(template);
+        self
+    }
+
+    pub fn adaptation(mut self, adaptation: AdaptationSet) -> PeriodBuilder {
+        self.period.adaptations.push(adaptation);
+        self
+    }
+
+    pub fn build(self) -> Period {
+        self.period
+    }
+}
+
+
+/// A builder for an `MPD`. The manifest is static unless specified otherwise with `mpd_type`.
+#[derive(Debug, Clone)]
+pub struct MpdBuilder {
+    mpd: MPD,
+}
+
+impl Default for MpdBuilder {
+    fn default() -> Self {
+        MpdBuilder {
+            mpd: MPD {
+                mpdtype: Some(String::from("static")),
+                xmlns: Some(String::from("urn:mpeg:dash:schema:mpd:2011")),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl MpdBuilder {
+    pub fn new() -> MpdBuilder {
+        MpdBuilder::default()
+    }
+
+    /// The Presentation Type, "static" or "dynamic".
+    pub fn mpd_type(mut self, mpd_type: &str) -> MpdBuilder {
+        self.mpd.mpdtype = Some(String::from(mpd_type));
+        self
+    }
+
+    /// A comma-separated list of profile URNs.
+    pub fn profiles(mut self, profiles: &str) -> MpdBuilder {
+        self.mpd.profiles = Some(String::from(profiles));
+        self
+    }
+
+    pub fn media_presentation_duration(mut self, duration: Duration) -> MpdBuilder {
+        self.mpd.mediaPresentationDuration = Some(duration);
+        self
+    }
+
+    pub fn min_buffer_time(mut self, duration: Duration) -> MpdBuilder {
+        self.mpd.minBufferTime = Some(duration);
+        self
+    }
+
+    /// Add a BaseURL (several BaseURLs can be added, for redundancy).
+    pub fn base_url(mut self, url: &str) -> MpdBuilder {
+        self.mpd.base_url.push(base_url(url));
+        self
+    }
+
+    pub fn location(mut self, url: &str) -> MpdBuilder {
+        self.mpd.locations.push(Location { url: String::from(url) });
+        self
+    }
+
+    pub fn period(mut self, period: Period) -> MpdBuilder {
+        self.mpd.periods.push(period);
+        self
+    }
+
+    pub fn build(self) -> MPD {
+        self.mpd
+    }
+}
@@ -7,49 +7,154 @@
 use std::fs::File;
 use std::io;
 use std::io::{BufReader, BufWriter};
+use std::path::Path;
 use std::process::Command;
 use crate::DashMpdError;
-use crate::fetch::DashDownloader;
+use crate::fetch::{Muxer, MuxMeta, MuxOptions};
+use crate::language::iso639_2_language;
 
 
+/// Muxing using the ffmpeg commandline application, run as a subprocess.
+pub struct FfmpegMuxer {
+    /// The location of the ffmpeg binary.
+    pub location: String,
+}
+
+/// Muxing using the VLC commandline application, run as a subprocess. Only produces MP4 output.
+pub struct VlcMuxer {
+    /// The location of the VLC binary.
+    pub location: String,
+}
+
+/// Muxing using mkvmerge (from the MKVToolNix suite), run as a subprocess. Produces Matroska or
+/// WebM output.
+pub struct MkvmergeMuxer {
+    /// The location of the mkvmerge binary.
+    pub location: String,
+}
+
+// Our muxers combine an audio stream and a video stream; single streams are copied to the output
+// file without muxing.
+fn audio_and_video<'a>(
+    muxer: &str,
+    audio: Option<&'a Path>,
+    video: Option<&'a Path>) -> Result<(&'a str, &'a str), DashMpdError> {
+    match (audio.and_then(Path::to_str), video.and_then(Path::to_str)) {
+        (Some(a), Some(v)) => Ok((a, v)),
+        _ => Err(DashMpdError::Muxing(format!("{muxer} muxer requires audio and video streams"))),
+    }
+}
+
+impl Muxer for FfmpegMuxer {
+    fn mux(&self, audio: Option<&Path>, video: Option<&Path>, out: &Path, meta: &MuxMeta) -> Result<(), DashMpdError> {
+        let (audio_path, video_path) = audio_and_video("ffmpeg", audio, video)?;
+        mux_audio_video_ffmpeg(&self.location, audio_path, video_path, out, meta)
+    }
+}
+
+impl Muxer for VlcMuxer {
+    fn mux(&self, audio: Option<&Path>, video: Option<&Path>, out: &Path, _meta: &MuxMeta) -> Result<(), DashMpdError> {
+        let (audio_path, video_path) = audio_and_video("vlc", audio, video)?;
+        mux_audio_video_vlc(&self.location, audio_path, video_path, out)
+    }
+}
+
+impl Muxer for MkvmergeMuxer {
+    fn mux(&self, audio: Option<&Path>, video: Option<&Path>, out: &Path, meta: &MuxMeta) -> Result<(), DashMpdError> {
+        let (audio_path, video_path) = audio_and_video("mkvmerge", audio, video)?;
+        mux_audio_video_mkvmerge(&self.location, audio_path, video_path, out, meta)
+    }
+}
+
+
+// The ffmpeg options which tag the audio track (the first input) and the subtitle tracks with their
+// language and title.
+fn ffmpeg_track_metadata(meta: &MuxMeta) -> Vec<String> {
+    let mut options = Vec::new();
+    if let Some(lang) = meta.audio_language.as_deref().and_then(iso639_2_language) {
+        options.extend([String::from("-metadata:s:a:0"), format!("language={lang}")]);
+    }
+    if let Some(title) = &meta.audio_title {
+        options.extend([String::from("-metadata:s:a:0"), format!("title={title}")]);
+    }
+    for (i, sub) in meta.subtitles.iter().enumerate() {
+        if let Some(lang) = sub.language.as_deref().and_then(iso639_2_language) {
+            options.extend([format!("-metadata:s:s:{i}"), format!("language={lang}")]);
+        }
+        if let Some(title) = &sub.title {
+            options.extend([format!("-metadata:s:s:{i}"), format!("title={title}")]);
+        }
+    }
+    options
+}
+
+// The mkvmerge options which tag track 0 of the next input file with a language and title.
+fn mkvmerge_track_metadata(language: Option<&str>, title: Option<&str>) -> Vec<String> {
+    let mut options = Vec::new();
+    if let Some(lang) = language.and_then(iso639_2_language) {
+        options.extend([String::from("--language"), format!("0:{lang}")]);
+    }
+    if let Some(title) = title {
+        options.extend([String::from("--track-name"), format!("0:{title}")]);
+    }
+    options
+}
+
 // ffmpeg can mux to many container types including mp4, mkv, avi
 fn mux_audio_video_ffmpeg(
-    downloader: &DashDownloader,
+    ffmpeg_location: &str,
     audio_path: &str,
-    video_path: &str) -> Result<(), DashMpdError> {
-    let output_path = downloader.output_path.as_ref()
-              .expect("muxer called without specifying output_path");
-    let container = match output_path.extension() {
-        Some(ext) => ext.to_str().unwrap_or("mp4"),
-        None => "mp4",
-    };
+    video_path: &str,
+    output_path: &Path,
+    meta: &MuxMeta) -> Result<(), DashMpdError> {
+    let container = meta.container.as_str();
+    let subtitles = &meta.subtitles;
     let tmpout = tempfile::Builder::new()
         .prefix("dashmpdrs")
         .suffix(&format!(".{container}"))
         .rand_bytes(5)
         .tempfile()
-        .map_err(|e| DashMpdError::Io(e, String::from("creating temporary output file")))?;
+        .map_err(|e| DashMpdError::io(e, "creating temporary output file"))?;
     let tmppath = tmpout
         .path()
         .to_str()
-        .ok_or_else(|| DashMpdError::Io(
-            io::Error::new(io::ErrorKind::Other, "obtaining tmpfile name"),
-            String::from("")))?;
-    let ffmpeg = Command::new(&downloader.ffmpeg_location)
-        .args(["-hide_banner",
-               "-nostats",
-               "-loglevel", "error",  // or "warning", "info"
-               "-y",  // overwrite output file if it exists
-               "-i", audio_path,
-               "-i", video_path,
-               "-c:v", "copy",
-               "-c:a", "copy",
-               "-movflags", "+faststart", "-preset", "veryfast",
-               // select the muxer explicitly
-               "-f", container,
-               tmppath])
+        .ok_or_else(|| DashMpdError::io(
+            io::Error::other("obtaining tmpfile name"), ""))?;
+    // Each subtitle file is an additional input, mapped to its own subtitle track in the output.
+    let mut subtitle_inputs = Vec::new();
+    let mut subtitle_options = Vec::new();
+    if !subtitles.is_empty() {
+        subtitle_options.extend(["-map", "0", "-map", "1"].map(String::from));
+    }
+    for (i, sub) in subtitles.iter().enumerate() {
+        subtitle_inputs.extend([String::from("-i"), sub.path.to_string_lossy().to_string()]);
+        subtitle_options.extend([String::from("-map"), (i + 2).to_string()]);
+    }
+    let metadata_options = ffmpeg_track_metadata(meta);
+    let mut args = vec!["-hide_banner",
+                        "-nostats",
+                        "-loglevel", "error",  // or "warning", "info"
+                        "-y",  // overwrite output file if it exists
+                        "-i", audio_path,
+                        "-i", video_path];
+    args.extend(subtitle_inputs.iter().map(String::as_str));
+    if container.eq("webm") {
+        // The WebM muxer only accepts VP8/VP9/AV1 video and Vorbis/Opus audio, which is what WebM-DASH
+        // streams contain, so we can always copy the streams without re-encoding.
+        args.extend(["-c", "copy"]);
+    } else {
+        args.extend(["-c:v", "copy",
+                     "-c:a", "copy",
+                     "-movflags", "+faststart", "-preset", "veryfast"]);
+    }
+    args.extend(subtitle_options.iter().map(String::as_str));
+    args.extend(metadata_options.iter().map(String::as_str));
+    // select the muxer explicitly
+    args.extend(["-f", container, tmppath]);
+    let ffmpeg = Command::new(ffmpeg_location)
+        .args(args)
         .output()
-        .map_err(|e| DashMpdError::Io(e, String::from("spawning ffmpeg subprocess")))?;
+        .map_err(|e| DashMpdError::io(e, "spawning ffmpeg subprocess"))?;
     let msg = String::from_utf8_lossy(&ffmpeg.stdout);
     if msg.len() > 0 {
         log::info!("ffmpeg stdout: {}", msg);
@@ -60,16 +165,16 @@ fn mux_audio_video_ffmpeg(
     }
     if ffmpeg.status.success() {
         let tmpfile = File::open(tmppath)
-            .map_err(|e| DashMpdError::Io(e, String::from("opening ffmpeg output")))?;
+            .map_err(|e| DashMpdError::io(e, "opening ffmpeg output"))?;
         let mut muxed = BufReader::new(tmpfile);
         let outfile = File::create(output_path)
-            .map_err(|e| DashMpdError::Io(e, String::from("creating output file")))?;
+            .map_err(|e| DashMpdError::io(e, "creating output file"))?;
         let mut sink = BufWriter::new(outfile);
         io::copy(&mut muxed, &mut sink)
-            .map_err(|e| DashMpdError::Io(e, String::from("copying ffmpeg output to output file")))?;
+            .map_err(|e| DashMpdError::io(e, "copying ffmpeg output to output file"))?;
         Ok(())
     } else {
-        Err(DashMpdError::Muxing(String::from("running ffmpeg")))
+        Err(DashMpdError::Muxing(format!("running ffmpeg: {msg}")))
     }
 }
 
@@ -77,24 +182,22 @@ fn mux_audio_video_ffmpeg(
 // See https://wiki.videolan.org/Transcode/
 // VLC could also mux to an mkv container if needed
 fn mux_audio_video_vlc(
-    downloader: &DashDownloader,
+    vlc_location: &str,
     audio_path: &str,
-    video_path: &str) -> Result<(), DashMpdError> {
-    let output_path = downloader.output_path.as_ref()
-              .expect("muxer called without specifying output_path");
+    video_path: &str,
+    output_path: &Path) -> Result<(), DashMpdError> {
     let tmpout = tempfile::Builder::new()
         .prefix("dashmpdrs")
         .suffix(".mp4")
         .rand_bytes(5)
         .tempfile()
-        .map_err(|e| DashMpdError::Io(e, String::from("creating temporary output file")))?;
+        .map_err(|e| DashMpdError::io(e, "creating temporary output file"))?;
     let tmppath = tmpout
         .path()
         .to_str()
-        .ok_or_else(|| DashMpdError::Io(
-            io::Error::new(io::ErrorKind::Other, "obtaining tmpfile name"),
-            String::from("")))?;
-    let vlc = Command::new(&downloader.vlc_location)
+        .ok_or_else(|| DashMpdError::io(
+            io::Error::other("obtaining tmpfile name"), ""))?;
+    let vlc = Command::new(vlc_location)
         .args(["-I", "dummy",
                "--no-repeat", "--no-loop",
                video_path,
@@ -104,16 +207,16 @@ fn mux_audio_video_vlc(
                "--sout-keep",
                "vlc://quit"])
         .output()
-        .map_err(|e| DashMpdError::Io(e, String::from("spawning VLC subprocess")))?;
+        .map_err(|e| DashMpdError::io(e, "spawning VLC subprocess"))?;
     if vlc.status.success() {
         let tmpfile = File::open(tmppath)
-            .map_err(|e| DashMpdError::Io(e, String::from("opening VLC output")))?;
+            .map_err(|e| DashMpdError::io(e, "opening VLC output"))?;
         let mut muxed = BufReader::new(tmpfile);
         let outfile = File::create(output_path)
-            .map_err(|e| DashMpdError::Io(e, String::from("creating output file")))?;
+            .map_err(|e| DashMpdError::io(e, "creating output file"))?;
         let mut sink = BufWriter::new(outfile);
         io::copy(&mut muxed, &mut sink)
-            .map_err(|e| DashMpdError::Io(e, String::from("copying VLC output to output file")))?;
+            .map_err(|e| DashMpdError::io(e, "copying VLC output to output file"))?;
         Ok(())
     } else {
         let msg = String::from_utf8_lossy(&vlc.stderr);
@@ -136,7 +239,7 @@ fn temporary_outpath(suffix: &str) -> Result<String, DashMpdError> {
         .suffix(suffix)
         .rand_bytes(5)
         .tempfile()
-        .map_err(|e| DashMpdError::Io(e, String::from("creating temporary output file")))?;
+        .map_err(|e| DashMpdError::io(e, "creating temporary output file"))?;
     match tmpout.path().to_str() {
         Some(s) => Ok(s.to_string()),
         None => Ok(format!("/tmp/dashmpdrs-tmp{suffix}")),
@@ -144,27 +247,41 @@ fn temporary_outpath(suffix: &str) -> Result<String, DashMpdError> {
 }
 
 fn mux_audio_video_mkvmerge(
-    downloader: &DashDownloader,
+    mkvmerge_location: &str,
     audio_path: &str,
-    video_path: &str) -> Result<(), DashMpdError> {
-    let output_path = downloader.output_path.as_ref()
-              .expect("muxer called without specifying output_path");
-    let tmppath = temporary_outpath(".mkv")?;
-    let mkv = Command::new(&downloader.mkvmerge_location)
-        .args(["--output", &tmppath,
-               "--no-video", audio_path,
-               "--no-audio", video_path])
+    video_path: &str,
+    output_path: &Path,
+    meta: &MuxMeta) -> Result<(), DashMpdError> {
+    let webm = meta.container.eq("webm");
+    let audio_args = mkvmerge_track_metadata(meta.audio_language.as_deref(), meta.audio_title.as_deref());
+    let mut subtitle_args = Vec::new();
+    for sub in &meta.subtitles {
+        subtitle_args.extend(mkvmerge_track_metadata(sub.language.as_deref(), sub.title.as_deref()));
+        subtitle_args.push(sub.path.to_string_lossy().to_string());
+    }
+    let tmppath = temporary_outpath(if webm { ".webm" } else { ".mkv" })?;
+    let mut args = vec!["--output", &tmppath];
+    if webm {
+        // Restrict the output to the WebM subset of Matroska
+        args.push("--webm");
+    }
+    args.push("--no-video");
+    args.extend(audio_args.iter().map(String::as_str));
+    args.extend([audio_path, "--no-audio", video_path]);
+    args.extend(subtitle_args.iter().map(String::as_str));
+    let mkv = Command::new(mkvmerge_location)
+        .args(args)
         .output()
-        .map_err(|e| DashMpdError::Io(e, String::from("spawning mkvmerge subprocess")))?;
+        .map_err(|e| DashMpdError::io(e, "spawning mkvmerge subprocess"))?;
     if mkv.status.success() {
         let tmpfile = File::open(&tmppath)
-            .map_err(|e| DashMpdError::Io(e, String::from("opening mkvmerge output")))?;
+            .map_err(|e| DashMpdError::io(e, "opening mkvmerge output"))?;
         let mut muxed = BufReader::new(tmpfile);
         let outfile = File::create(output_path)
-            .map_err(|e| DashMpdError::Io(e, String::from("opening output file")))?;
+            .map_err(|e| DashMpdError::io(e, "opening output file"))?;
         let mut sink = BufWriter::new(outfile);
         io::copy(&mut muxed, &mut sink)
-            .map_err(|e| DashMpdError::Io(e, String::from("copying mkvmerge output to output file")))?;
+            .map_err(|e| DashMpdError::io(e, "copying mkvmerge output to output file"))?;
 	#[cfg(target_os = "windows")]
 	::std::fs::remove_file(tmppath).ok();
         Ok(())
@@ -176,18 +293,15 @@ fn mux_audio_video_mkvmerge(
 }
 
 
-// First try ffmpeg subprocess, if that fails try vlc subprocess
+// Try the available muxers in order of preference for the output container, until one succeeds.
 pub fn mux_audio_video(
-    downloader: &DashDownloader,
+    opts: &MuxOptions,
     audio_path: &str,
-    video_path: &str) -> Result<(), DashMpdError> {
+    video_path: &str,
+    output_path: &Path,
+    meta: &MuxMeta) -> Result<(), DashMpdError> {
     log::trace!("Muxing audio {audio_path}, video {video_path}");
-    let output_path = downloader.output_path.as_ref()
-              .expect("muxer called without specifying output_path");
-    let container = match output_path.extension() {
-        Some(ext) => ext.to_str().unwrap_or("mp4"),
-        None => "mp4",
-    };
+    let container = meta.container.as_str();
     let mut muxer_preference = vec![];
     if container.eq("mkv") {
         muxer_preference.push("mkvmerge");
@@ -195,36 +309,59 @@ pub fn mux_audio_video(
     } else if container.eq("mp4") {
         muxer_preference.push("ffmpeg");
         muxer_preference.push("vlc");
+    } else if container.eq("webm") {
+        // mkvmerge handles WebM natively, as a restricted form of Matroska
+        muxer_preference.push("ffmpeg");
+        muxer_preference.push("mkvmerge");
     } else {
         muxer_preference.push("ffmpeg");
     }
     log::info!("Muxer preference for {container} is {muxer_preference:?}");
-    for muxer in muxer_preference {
-        log::info!("Trying muxer {}", muxer);
-        if muxer.eq("mkvmerge") {
-            if let Err(e) =  mux_audio_video_mkvmerge(downloader, audio_path, video_path) {
-                log::warn!("Muxing with mkvmerge subprocess failed: {e}");
-            } else {
-                log::info!("Muxing with mkvmerge subprocess succeeded");
-                return Ok(());
-            }
-        } else if muxer.eq("ffmpeg") {
-            if let Err(e) = mux_audio_video_ffmpeg(downloader, audio_path, video_path) {
-                log::warn!("Muxing with ffmpeg subprocess failed: {e}");
-            } else {
-                log::info!("Muxing with ffmpeg subprocess succeeded");
-                return Ok(());
-            }
-        } else if muxer.eq("vlc") {
-            if let Err(e) = mux_audio_video_vlc(downloader, audio_path, video_path) {
-                log::warn!("Muxing with vlc subprocess failed: {e}");
-            } else {
-                log::info!("Muxing with vlc subprocess succeeded");
-                return Ok(());
-            }
+    let mut failures = Vec::new();
+    for name in muxer_preference {
+        log::info!("Trying muxer {}", name);
+        let muxer: Box<dyn Muxer> = match name {
+            "mkvmerge" => Box::new(MkvmergeMuxer { location: opts.mkvmerge_location.clone() }),
+            "vlc" => Box::new(VlcMuxer { location: opts.vlc_location.clone() }),
+            _ => Box::new(FfmpegMuxer { location: opts.ffmpeg_location.clone() }),
+        };
+        if let Err(e) = muxer.mux(Some(Path::new(audio_path)), Some(Path::new(video_path)), output_path, meta) {
+            log::warn!("Muxing with {name} subprocess failed: {e}");
+            failures.push(e.to_string());
+        } else {
+            log::info!("Muxing with {name} subprocess succeeded");
+            return Ok(());
         }
     }
     log::warn!("All available muxers failed");
-    Err(DashMpdError::Muxing(String::from("all available muxers failed")))
+    Err(DashMpdError::Muxing(format!("all available muxers failed ({})", failures.join("; "))))
 }
 
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_track_metadata_options() {
+        use std::path::PathBuf;
+        use crate::fetch::{MuxMeta, SubtitleFile};
+        use super::{ffmpeg_track_metadata, mkvmerge_track_metadata};
+
+        let meta = MuxMeta {
+            audio_language: Some(String::from("de")),
+            audio_title: Some(String::from("commentary")),
+            subtitles: vec![SubtitleFile {
+                path: PathBuf::from("/tmp/subs.vtt"),
+                language: Some(String::from("fr-CA")),
+                title: None,
+                format: String::from("vtt"),
+            }],
+            ..MuxMeta::default()
+        };
+        assert_eq!(ffmpeg_track_metadata(&meta),
+                   ["-metadata:s:a:0", "language=deu", "-metadata:s:a:0", "title=commentary",
+                    "-metadata:s:s:0", "language=fra"]);
+        assert_eq!(mkvmerge_track_metadata(Some("de"), Some("commentary")),
+                   ["--language", "0:deu", "--track-name", "0:commentary"]);
+        assert!(mkvmerge_track_metadata(Some("und"), None).is_empty());
+    }
+}
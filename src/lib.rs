@@ -40,7 +40,8 @@
 //!
 //! - Dynamic MPD manifests, that are used for live streaming/OTT TV
 //! - Encrypted content using DRM such as Encrypted Media Extensions (EME) and Media Source Extension (MSE)
-//! - Subtitles (eg. WebVTT and TTML streams)
+//! - Subtitles that are split into segments (subtitles available as a single WebVTT or TTML file are
+//!   supported)
 //! - XLink with actuate=onRequest
 //
 //
@@ -67,7 +68,14 @@ mod libav;
 #[cfg(all(feature = "fetch", not(feature = "libav")))]
 mod ffmpeg;
 #[cfg(feature = "fetch")]
+mod language;
+#[cfg(feature = "fetch")]
 pub mod fetch;
+pub mod ttml;
+pub mod sidx;
+mod mp4;
+pub mod timeline;
+pub mod builder;
 
 #[cfg(all(feature = "fetch", feature = "libav"))]
 use crate::libav::mux_audio_video;
@@ -77,7 +85,9 @@ use serde::{Serialize, Serializer, Deserialize};
 use serde::de;
 use serde_with::skip_serializing_none;
 use regex::Regex;
+use once_cell::sync::Lazy;
 use std::time::Duration;
+use std::collections::HashMap;
 use chrono::DateTime;
 
 
@@ -100,10 +110,36 @@ pub enum DashMpdError {
     Network(String),
     #[error("muxing error {0}")]
     Muxing(String),
+    #[error("configuration error {0}")]
+    Config(String),
+    // The sizes are 0 when unknown, for example when the disk fills up during the download.
+    #[error("insufficient disk space")]
+    InsufficientDiskSpace { needed: u64, available: u64 },
+    // Muxing failed after the audio and video streams were downloaded. The streams have been moved
+    // to these files, so that they can be muxed again with fetch::mux_streams.
+    #[error("muxing error {message} (downloaded streams preserved for retrying)")]
+    MuxingFailed { message: String, audio: Option<std::path::PathBuf>, video: Option<std::path::PathBuf> },
     #[error("unknown error {0}")]
     Other(String),
 }
 
+impl DashMpdError {
+    /// A parsing error, with `context` describing what we were trying to parse.
+    pub fn parsing(context: &str, e: impl std::error::Error) -> Self {
+        DashMpdError::Parsing(format!("{context}: {e}"))
+    }
+
+    /// A network error, with `context` describing the failed request.
+    pub fn network(context: &str, e: impl std::error::Error) -> Self {
+        DashMpdError::Network(format!("{context}: {e}"))
+    }
+
+    /// An I/O error, with `context` describing the failed operation.
+    pub fn io(e: std::io::Error, context: &str) -> Self {
+        DashMpdError::Io(e, String::from(context))
+    }
+}
+
 
 // Parse an XML duration string, as per https://www.w3.org/TR/xmlschema-2/#duration
 //
@@ -120,18 +156,21 @@ pub enum DashMpdError {
 //
 // Limitations: we can't represent negative durations (leading "-" character) due to the choice of a
 // std::time::Duration. We only accept fractional parts of seconds, and reject for example "P0.5Y" and "PT2.3H". 
+static XS_DURATION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(r"^(?P<sign>[+-])?P",
+                       r"(?:(?P<years>\d+)Y)?",
+                       r"(?:(?P<months>\d+)M)?",
+                       r"(?:(?P<weeks>\d+)W)?",
+                       r"(?:(?P<days>\d+)D)?",
+                       r"(?:(?P<hastime>T)", // time part must begin with a T
+                       r"(?:(?P<hours>\d+)H)?",
+                       r"(?:(?P<minutes>\d+)M)?",
+                       r"(?:(?P<seconds>\d+)(?:(?P<nanoseconds>[.,]\d+)?)S)?",
+                       r")?")).unwrap()
+});
+
 fn parse_xs_duration(s: &str) -> Result<Duration, DashMpdError> {
-    let re = Regex::new(concat!(r"^(?P<sign>[+-])?P",
-                                r"(?:(?P<years>\d+)Y)?",
-                                r"(?:(?P<months>\d+)M)?",
-                                r"(?:(?P<weeks>\d+)W)?",
-                                r"(?:(?P<days>\d+)D)?",
-                                r"(?:(?P<hastime>T)", // time part must begin with a T
-                                r"(?:(?P<hours>\d+)H)?",
-                                r"(?:(?P<minutes>\d+)M)?",
-                                r"(?:(?P<seconds>\d+)(?:(?P<nanoseconds>[.,]\d+)?)S)?",
-                                r")?")).unwrap();
-    match re.captures(s) {
+    match XS_DURATION_RE.captures(s) {
         Some(m) => {
             if m.name("hastime").is_none() &&
                m.name("years").is_none() &&
@@ -254,6 +293,15 @@ where
     }
 }
 
+// Deserialize element content with leading and trailing whitespace removed.
+fn deserialize_trimmed_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(String::from(s.trim()))
+}
+
 fn serialize_xs_duration<S>(oxs: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -318,9 +366,27 @@ pub struct ProgramInformation {
     pub Copyright: Option<Copyright>,
     /// Language in RFC 5646 format
     pub lang: Option<String>,
+    /// A URL where further information concerning the media content can be found.
     pub moreInformationURL: Option<String>,
 }
 
+impl ProgramInformation {
+    /// The title of the media content, if specified.
+    pub fn title(&self) -> Option<&str> {
+        self.Title.as_ref().and_then(|t| t.content.as_deref())
+    }
+
+    /// The original source of the media content, if specified.
+    pub fn source(&self) -> Option<&str> {
+        self.Source.as_ref().and_then(|s| s.content.as_deref())
+    }
+
+    /// The copyright statement concerning the media content, if specified.
+    pub fn copyright(&self) -> Option<&str> {
+        self.Copyright.as_ref().and_then(|c| c.content.as_deref())
+    }
+}
+
 /// Describes a sequence of contiguous Segments with identical duration.
 #[skip_serializing_none]
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -340,7 +406,7 @@ pub struct S {
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct SegmentTimeline {
-    #[serde(rename = "S")]
+    #[serde(rename = "S", alias = "segments")]
     pub segments: Vec<S>,
 }
 
@@ -354,6 +420,30 @@ pub struct Initialization {
     pub range: Option<String>,
 }
 
+/// A time range of failover content, in units of the timescale of the enclosing element.
+#[skip_serializing_none]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FCS {
+    /// The presentation time at which the range starts
+    pub t: u64,
+    /// The duration of the range; if absent, the range extends to the next FCS or the end of the Period
+    pub d: Option<u64>,
+}
+
+/// Time ranges in which the content is known to be missing or replaced by failover content (such
+/// as a slate), as declared in DVB-DASH manifests.
+#[skip_serializing_none]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FailoverContent {
+    /// If false, the media segments in these ranges are not available at all; if true (the
+    /// default), they contain failover content.
+    pub valid: Option<bool>,
+    #[serde(rename = "FCS", alias = "fcs")]
+    pub fcs: Vec<FCS>,
+}
+
 /// Allows template-based `SegmentURL` construction. Specifies various substitution rules using
 /// dynamic values such as `$Time$` and `$Number$` that map to a sequence of Segments.
 #[skip_serializing_none]
@@ -373,7 +463,17 @@ pub struct SegmentTemplate {
     /// Indicates a possible offset between media segment start/end points and period start/end points.
     pub eptDelta: Option<i64>,
     pub presentationTimeOffset: Option<u64>,
+    /// If true, media segments from different Representations (and successive Periods) can be
+    /// concatenated after a single initialization segment.
     pub bitstreamSwitching: Option<bool>,
+    /// The number of seconds before its nominal availability time that a segment becomes available
+    /// (used by low-latency live streams, whose segments are delivered using chunked transfer
+    /// encoding while they are being produced).
+    pub availabilityTimeOffset: Option<f64>,
+    /// If false, segments may still be in production when they are requested, and should be
+    /// fetched progressively.
+    pub availabilityTimeComplete: Option<bool>,
+    pub FailoverContent: Option<FailoverContent>,
 }
 
 /// A URI string to which a new request for an updated manifest should be made. This feature is
@@ -390,7 +490,8 @@ pub struct Location {
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct BaseURL {
-    #[serde(rename = "$value")]
+    /// The URL, with any leading and trailing whitespace (from pretty-printed XML) removed.
+    #[serde(rename = "$value", deserialize_with = "deserialize_trimmed_string")]
     pub base: String,
     /// Elements with the same `@serviceLocation` value are likely to have their URLs resolve to
     /// services at a common network location, for example the same CDN.
@@ -402,7 +503,7 @@ pub struct BaseURL {
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct SegmentBase {
-    #[serde(rename = "Initialization")]
+    #[serde(rename = "Initialization", alias = "initialization")]
     pub initialization: Option<Initialization>,
     pub timescale: Option<u64>,
     pub presentationTimeOffset: Option<u64>,
@@ -410,6 +511,7 @@ pub struct SegmentBase {
     pub indexRangeExact: Option<bool>,
     pub availabilityTimeOffset: Option<f64>,
     pub availabilityTimeComplete: Option<bool>,
+    pub FailoverContent: Option<FailoverContent>,
 }
 
 /// The URL of a media segment.
@@ -440,7 +542,7 @@ pub struct SegmentList {
     #[serde(rename = "xlink:show")]
     pub show: Option<String>,
     pub Initialization: Option<Initialization>,
-    #[serde(rename = "SegmentURL")]
+    #[serde(rename = "SegmentURL", alias = "segment_urls")]
     pub segment_urls: Vec<SegmentURL>,
 }
 
@@ -451,7 +553,7 @@ pub struct Resync {
     pub dT: Option<u64>,
     pub dImax: Option<u64>,
     pub dImin: Option<u64>,
-    #[serde(rename = "type")]
+    #[serde(rename = "type", alias = "rtype")]
     pub rtype: Option<String>,
 }
 
@@ -495,10 +597,14 @@ pub struct Representation {
     pub frameRate: Option<String>, // can be something like "15/2"
     pub sar: Option<String>,
     pub bandwidth: Option<u64>,
+    /// Relative preference for this Representation, as a hint from the packager (higher values are
+    /// preferred, the default is 1).
+    pub selectionPriority: Option<u64>,
     pub audioSamplingRate: Option<u64>,
     pub width: Option<u64>,
     pub height: Option<u64>,
     pub startWithSAP: Option<u64>,
+    #[serde(alias = "base_url")]
     pub BaseURL: Vec<BaseURL>,
     pub AudioChannelConfiguration: Option<AudioChannelConfiguration>,
     pub mediaStreamStructureId: Option<String>,
@@ -509,6 +615,11 @@ pub struct Representation {
     /// A "remote resource", following the XML Linking Language (XLink) specification.
     #[serde(rename = "xlink:href")]
     pub href: Option<String>,
+    /// Properties that a client must understand in order to use this Representation.
+    #[serde(rename = "EssentialProperty", alias = "essential_property")]
+    pub essential_property: Vec<EssentialProperty>,
+    #[serde(rename = "SupplementalProperty", alias = "supplemental_property")]
+    pub supplemental_property: Vec<SupplementalProperty>,
 }
 
 /// Describes a media content component.
@@ -522,7 +633,7 @@ pub struct ContentComponent {
     pub contentType: Option<String>,
     pub par: Option<String>,
     pub tag: Option<String>,
-    pub Accessibility: Option<Accessibility>,
+    pub Accessibility: Vec<Accessibility>,
 }
 
 /// A Common Encryption "Protection System Specific Header" box. Content is typically base64 encoded.
@@ -534,6 +645,27 @@ pub struct CencPssh {
     pub content: Option<String>,
 }
 
+/// A Microsoft PlayReady Object (the mspr:pro element), base64 encoded. Its PlayReady Header may
+/// contain the license acquisition URL; see [ContentProtection::license_url].
+#[skip_serializing_none]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MsprPro {
+    #[serde(rename = "$value")]
+    pub content: Option<String>,
+}
+
+/// A license acquisition URL hint, as carried by the dashif:laurl and clearkey:Laurl elements.
+#[skip_serializing_none]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Laurl {
+    #[serde(rename = "Lic_type", alias = "licenseType")]
+    pub licenseType: Option<String>,
+    #[serde(rename = "$value")]
+    pub content: Option<String>,
+}
+
 /// Contains information on DRM (rights management / encryption) mechanisms used in the stream, such
 /// as Widevine and Playready. Note that this library is not able to download content with DRM. If
 /// this node is not present, no content protection is applied by the source.
@@ -543,17 +675,83 @@ pub struct CencPssh {
 pub struct ContentProtection {
     pub robustness: Option<String>,
     pub refId: Option<String>,
-    #[serde(rename = "ref")]
+    #[serde(rename = "ref", alias = "cpref")]
     pub cpref: Option<String>,
     pub schemeIdUri: Option<String>,
     // In fact will be cenc:pssh, where cenc is the urn:mpeg:cenc:2013 XML namespace, but the serde
     // crate doesn't support XML namespaces
-    #[serde(rename = "pssh")]
+    #[serde(rename = "pssh", alias = "cenc_pssh")]
     pub cenc_pssh: Option<CencPssh>,
     // the DRM key identifier
     #[serde(rename = "cenc:default_KID")]
     pub default_KID: Option<String>,
     pub value: Option<String>,
+    // The mspr:pro element used by PlayReady
+    #[serde(rename = "pro", alias = "msprpro")]
+    pub msprpro: Option<MsprPro>,
+    // The dashif:laurl element (DASH-IF IOP license server URL)
+    #[serde(rename = "laurl")]
+    pub laurl: Option<Laurl>,
+    // The clearkey:Laurl element
+    #[serde(rename = "Laurl", alias = "clearkey_laurl")]
+    pub clearkey_laurl: Option<Laurl>,
+}
+
+impl ContentProtection {
+    /// The license acquisition URL advertised for this DRM system, if any. This is taken from a
+    /// dashif:laurl or clearkey:Laurl child element, or failing that from the LA_URL element of the
+    /// PlayReady Header embedded in a mspr:pro PlayReady Object.
+    pub fn license_url(&self) -> Option<String> {
+        let explicit = self.laurl.as_ref()
+            .or(self.clearkey_laurl.as_ref())
+            .and_then(|l| l.content.as_ref())
+            .map(|u| u.trim())
+            .filter(|u| !u.is_empty());
+        if let Some(u) = explicit {
+            return Some(u.to_string());
+        }
+        self.msprpro.as_ref()
+            .and_then(|pro| pro.content.as_ref())
+            .and_then(|b64| playready_header(b64))
+            .and_then(|xml| playready_la_url(&xml))
+    }
+}
+
+// Decode a base64-encoded PlayReady Object and return the XML of its PlayReady Header (record type
+// 1), which is encoded in UTF-16LE. The object starts with a 32-bit little-endian total length and a
+// 16-bit record count, followed by records each with a 16-bit type and a 16-bit length.
+fn playready_header(b64: &str) -> Option<String> {
+    use base64::Engine;
+
+    let compact: String = b64.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let pro = base64::engine::general_purpose::STANDARD.decode(compact).ok()?;
+    let u16_at = |i: usize| pro.get(i..i+2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let count = u16_at(4)?;
+    let mut pos = 6;
+    for _ in 0..count {
+        let rtype = u16_at(pos)?;
+        let rlen = usize::from(u16_at(pos + 2)?);
+        let data = pro.get(pos+4..pos+4+rlen)?;
+        if rtype == 1 {
+            let utf16: Vec<u16> = data.chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            return String::from_utf16(&utf16).ok();
+        }
+        pos += 4 + rlen;
+    }
+    None
+}
+
+fn playready_la_url(header: &str) -> Option<String> {
+    let start = header.find("<LA_URL>")? + "<LA_URL>".len();
+    let end = start + header[start..].find("</LA_URL>")?;
+    let url = header[start..end].trim();
+    if url.is_empty() {
+        None
+    } else {
+        Some(url.replace("&amp;", "&"))
+    }
 }
 
 /// The purpose of this media stream, such as captions, subtitle, main, alternate, supplementary,
@@ -586,7 +784,7 @@ pub struct Binary {
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Signal {
-    #[serde(rename = "Binary")]
+    #[serde(rename = "Binary", alias = "content")]
     pub content: Vec<Binary>,
 }
 
@@ -599,7 +797,7 @@ pub struct Event {
     pub presentationTime: Option<u64>,
     pub duration: Option<u64>,
     pub timescale: Option<u64>,
-    #[serde(rename = "Signal")]
+    #[serde(rename = "Signal", alias = "signal")]
     pub signal: Vec<Signal>,
 }
 
@@ -609,7 +807,7 @@ pub struct Event {
 pub struct EventStream {
     pub timescale: Option<u64>,
     pub schemeIdUri: Option<String>,
-    #[serde(rename = "Event")]
+    #[serde(rename = "Event", alias = "event")]
     pub event: Vec<Event>,
 }
 
@@ -645,7 +843,9 @@ pub struct Label {
 #[serde(default)]
 pub struct AdaptationSet {
     pub id: Option<i64>,
+    #[serde(rename = "Label", alias = "label")]
     pub label: Option<Label>,
+    #[serde(alias = "base_url")]
     pub BaseURL: Vec<BaseURL>,
     /// A "remote resource", following the XML Linking Language (XLink) specification.
     #[serde(rename = "xlink:href")]
@@ -670,6 +870,9 @@ pub struct AdaptationSet {
     pub codecs: Option<String>,
     pub minBandwidth: Option<u64>,
     pub maxBandwidth: Option<u64>,
+    /// The width of the video, if it is the same for all Representations
+    pub width: Option<u64>,
+    pub height: Option<u64>,
     pub minWidth: Option<u64>,
     pub maxWidth: Option<u64>,
     pub minHeight: Option<u64>,
@@ -679,12 +882,85 @@ pub struct AdaptationSet {
     pub SegmentList: Option<SegmentList>,
     pub ContentComponent: Vec<ContentComponent>,
     pub ContentProtection: Vec<ContentProtection>,
-    pub Accessibility: Option<Accessibility>,
+    pub Accessibility: Vec<Accessibility>,
+    pub Role: Vec<Role>,
     pub AudioChannelConfiguration: Option<AudioChannelConfiguration>,
-    #[serde(rename = "Representation")]
+    /// Properties that a client must understand in order to use this AdaptationSet.
+    #[serde(rename = "EssentialProperty", alias = "essential_property")]
+    pub essential_property: Vec<EssentialProperty>,
+    #[serde(rename = "SupplementalProperty", alias = "supplemental_property")]
+    pub supplemental_property: Vec<SupplementalProperty>,
+    #[serde(rename = "Representation", alias = "representations")]
     pub representations: Vec<Representation>,
 }
 
+impl AdaptationSet {
+    /// The Spatial Relationship Description of the content of this AdaptationSet, signalled by a
+    /// SupplementalProperty or EssentialProperty with scheme `urn:mpeg:dash:srd:2014`.
+    pub fn spatial_relationship(&self) -> Option<SpatialRelationship> {
+        let supplemental = self.supplemental_property.iter()
+            .map(|p| (p.schemeIdUri.as_str(), p.value.as_deref()));
+        let essential = self.essential_property.iter()
+            .map(|p| (p.schemeIdUri.as_str(), p.value.as_deref()));
+        supplemental.chain(essential)
+            .filter(|(scheme, _)| *scheme == "urn:mpeg:dash:srd:2014")
+            .find_map(|(_, value)| value.and_then(SpatialRelationship::from_value))
+    }
+}
+
+/// A Spatial Relationship Description (SRD), which positions the video content of an AdaptationSet
+/// (for example one tile of a larger picture) in a reference space, in arbitrary units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpatialRelationship {
+    pub source_id: u64,
+    pub object_x: u64,
+    pub object_y: u64,
+    pub object_width: u64,
+    pub object_height: u64,
+    /// The size of the reference space (optional in the SRD).
+    pub total_width: Option<u64>,
+    pub total_height: Option<u64>,
+    pub spatial_set_id: Option<u64>,
+}
+
+impl SpatialRelationship {
+    /// Parse the value of an SRD descriptor, a comma-separated list of integers `source_id,
+    /// object_x, object_y, object_width, object_height[, total_width, total_height[, spatial_set_id]]`.
+    pub fn from_value(value: &str) -> Option<SpatialRelationship> {
+        let fields = value.split(',')
+            .map(|f| f.trim().parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .ok()?;
+        let (total_width, total_height, spatial_set_id) = match fields.len() {
+            5 => (None, None, None),
+            7 => (Some(fields[5]), Some(fields[6]), None),
+            8 => (Some(fields[5]), Some(fields[6]), Some(fields[7])),
+            _ => return None,
+        };
+        Some(SpatialRelationship {
+            source_id: fields[0],
+            object_x: fields[1],
+            object_y: fields[2],
+            object_width: fields[3],
+            object_height: fields[4],
+            total_width,
+            total_height,
+            spatial_set_id,
+        })
+    }
+
+    /// Returns `true` if the object covers the whole reference space, and `false` for a partial tile
+    /// or if the size of the reference space isn't known (see `Period::spatial_relationship`, which
+    /// determines it from the other SRDs of the Period).
+    pub fn covers_full_frame(&self) -> bool {
+        match (self.total_width, self.total_height) {
+            (Some(w), Some(h)) => self.object_x == 0 && self.object_y == 0 &&
+                self.object_width >= w && self.object_height >= h,
+            _ => false,
+        }
+    }
+}
+
 /// Identifies the asset to which a given Period belongs. Can be used to implement
 /// client functionality that depends on distinguishing between ads and main content.
 #[skip_serializing_none]
@@ -708,6 +984,7 @@ pub struct Period {
     #[serde(serialize_with = "serialize_xs_duration")]
     pub duration: Option<Duration>,
     pub bitstreamSwitching: Option<bool>,
+    #[serde(alias = "base_url")]
     pub BaseURL: Vec<BaseURL>,
     /// A "remote resource", following the XML Linking Language (XLink) specification.
     #[serde(rename = "xlink:href")]
@@ -715,36 +992,69 @@ pub struct Period {
     #[serde(rename = "xlink:actuate")]
     pub actuate: Option<String>,
     pub SegmentTemplate: Option<SegmentTemplate>,
-    #[serde(rename = "AdaptationSet")]
+    #[serde(rename = "AdaptationSet", alias = "adaptations")]
     pub adaptations: Vec<AdaptationSet>,
     pub asset_identifier: Option<AssetIdentifier>,
 }
 
+impl Period {
+    /// The Spatial Relationship Description of the content of an AdaptationSet of this Period. The
+    /// size of the reference space is optional in an SRD; when it is absent, it is inherited from
+    /// the SRD of another AdaptationSet of the Period with the same `source_id`.
+    pub fn spatial_relationship(&self, adaptation: &AdaptationSet) -> Option<SpatialRelationship> {
+        let mut srd = adaptation.spatial_relationship()?;
+        if srd.total_width.is_none() || srd.total_height.is_none() {
+            let reference = self.adaptations.iter()
+                .filter_map(AdaptationSet::spatial_relationship)
+                .find(|other| other.source_id == srd.source_id &&
+                      other.total_width.is_some() && other.total_height.is_some());
+            if let Some(reference) = reference {
+                srd.total_width = reference.total_width;
+                srd.total_height = reference.total_height;
+            }
+        }
+        Some(srd)
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Reporting {
     pub schemeIdUri: Option<String>,
     pub value: Option<String>,
+    /// The URL to which metrics are reported.
     #[serde(rename = "dvb:reportingUrl")]
     pub reportingUrl: Option<String>,
+    /// The interval between reports, in seconds.
+    pub reportingInterval: Option<u64>,
     #[serde(rename = "dvb:probability")]
     pub probability: Option<u64>,
 }
 
+/// The time range of the presentation during which metrics are collected.
+#[skip_serializing_none]
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Range {
+    #[serde(deserialize_with = "deserialize_xs_duration", default)]
+    #[serde(serialize_with = "serialize_xs_duration")]
     pub starttime: Option<Duration>,
+    #[serde(deserialize_with = "deserialize_xs_duration", default)]
+    #[serde(serialize_with = "serialize_xs_duration")]
     pub duration: Option<Duration>,
 }
 
+/// A request from the content provider for the client to report download metrics.
 #[skip_serializing_none]
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Metrics {
+    /// The list of metrics to be reported (eg. "DVBErrors").
     pub metrics: String,
+    #[serde(rename = "Reporting", alias = "reporting")]
     pub reporting: Vec<Reporting>,
+    #[serde(rename = "Range", alias = "range")]
     pub range: Vec<Range>,
 }
 
@@ -795,17 +1105,22 @@ pub struct LeapSecondInformation {
 }
 
 /// The root node of a parsed DASH MPD manifest.
+///
+/// The `Debug` output only includes the top-level attributes and the number of child elements,
+/// since the full element tree of a large manifest is unreadable.
 #[skip_serializing_none]
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct MPD {
     /// The Presentation Type, either "static" or "dynamic" (a live stream for which segments become
     /// available over time).
-    #[serde(rename = "type")]
+    #[serde(rename = "type", alias = "mpdtype")]
     pub mpdtype: Option<String>,
     pub xmlns: Option<String>,
     #[serde(rename = "xsi:schemaLocation")]
     pub schemaLocation: Option<String>,
+    /// A comma-separated list of the profiles to which the manifest conforms (see the `profiles`
+    /// method for a typed representation).
     pub profiles: Option<String>,
     /// Prescribes how many seconds of buffer a client should keep to avoid stalling when streaming
     /// under ideal network conditions with bandwidth matching the @bandwidth attribute.
@@ -821,6 +1136,7 @@ pub struct MPD {
     #[serde(deserialize_with = "deserialize_xs_duration", default)]
     #[serde(serialize_with = "serialize_xs_duration")]
     pub mediaPresentationDuration: Option<Duration>,
+    /// The maximal duration of any segment in the presentation.
     #[serde(deserialize_with = "deserialize_xs_duration", default)]
     #[serde(serialize_with = "serialize_xs_duration")]
     pub maxSegmentDuration: Option<Duration>,
@@ -831,11 +1147,12 @@ pub struct MPD {
     pub publishTime: Option<XsDatetime>,
     pub availabilityStartTime: Option<XsDatetime>,
     pub availabilityEndTime: Option<XsDatetime>,
-    #[serde(rename = "Period", default)]
+    #[serde(rename = "Period", alias = "periods", default)]
     pub periods: Vec<Period>,
     /// There may be several BaseURLs, for redundancy (for example multiple CDNs)
-    #[serde(rename = "BaseURL")]
+    #[serde(rename = "BaseURL", alias = "base_url")]
     pub base_url: Vec<BaseURL>,
+    #[serde(rename = "Location", alias = "locations")]
     pub locations: Vec<Location>,
     pub ServiceDescription: Option<ServiceDescription>,
     pub ProgramInformation: Option<ProgramInformation>,
@@ -843,22 +1160,279 @@ pub struct MPD {
     pub UTCTiming: Vec<UTCTiming>,
     /// Correction for leap seconds, used by the DASH Low Latency specification. 
     pub LeapSecondInformation: Option<LeapSecondInformation>,
-    #[serde(rename = "EssentialProperty")]
+    #[serde(rename = "EssentialProperty", alias = "essential_property")]
     pub essential_property: Vec<EssentialProperty>,
-    #[serde(rename = "SupplementalProperty")]
+    #[serde(rename = "SupplementalProperty", alias = "supplemental_property")]
     pub supplemental_property: Vec<SupplementalProperty>,
 }
 
 
+/// A DASH profile, which identifies the subset of the DASH specification (and of the media
+/// segment formats) used by a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Profile {
+    /// `urn:mpeg:dash:profile:full:2011`
+    Full,
+    /// `urn:mpeg:dash:profile:isoff-on-demand:2011` and the extended on-demand profile
+    IsoffOnDemand,
+    /// `urn:mpeg:dash:profile:isoff-live:2011` and the extended live profile
+    IsoffLive,
+    /// `urn:mpeg:dash:profile:isoff-main:2011`
+    IsoffMain,
+    /// `urn:mpeg:dash:profile:mp2t-main:2011`
+    Mp2tMain,
+    /// `urn:mpeg:dash:profile:mp2t-simple:2011`
+    Mp2tSimple,
+    /// `urn:mpeg:dash:profile:cmaf:2019`
+    Cmaf,
+    /// The DVB-DASH profiles (`urn:dvb:dash:profile:dvb-dash:2014` and its variants)
+    DvbDash,
+    /// `urn:hbbtv:dash:profile:isoff-live:2012`
+    HbbTV,
+    /// Any other profile identifier.
+    Other(String),
+}
+
+impl Profile {
+    /// Identify the profile designated by a URN.
+    pub fn from_urn(urn: &str) -> Profile {
+        match urn.trim() {
+            "urn:mpeg:dash:profile:full:2011" => Profile::Full,
+            "urn:mpeg:dash:profile:isoff-on-demand:2011" |
+            "urn:mpeg:dash:profile:isoff-ext-on-demand:2014" => Profile::IsoffOnDemand,
+            "urn:mpeg:dash:profile:isoff-live:2011" |
+            "urn:mpeg:dash:profile:isoff-ext-live:2014" => Profile::IsoffLive,
+            "urn:mpeg:dash:profile:isoff-main:2011" => Profile::IsoffMain,
+            "urn:mpeg:dash:profile:mp2t-main:2011" => Profile::Mp2tMain,
+            "urn:mpeg:dash:profile:mp2t-simple:2011" => Profile::Mp2tSimple,
+            "urn:mpeg:dash:profile:cmaf:2019" => Profile::Cmaf,
+            "urn:hbbtv:dash:profile:isoff-live:2012" => Profile::HbbTV,
+            u if u.starts_with("urn:dvb:dash:profile:dvb-dash:") => Profile::DvbDash,
+            u => Profile::Other(u.to_string()),
+        }
+    }
+
+    /// Returns `true` for the MPEG-2 Transport Stream profiles, whose (possibly multiplexed)
+    /// segments are handled poorly by the `fetch` module.
+    pub fn is_mp2t(&self) -> bool {
+        matches!(self, Profile::Mp2tMain | Profile::Mp2tSimple)
+    }
+}
+
+impl std::fmt::Debug for MPD {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MPD")
+            .field("mpdtype", &self.mpdtype)
+            .field("mediaPresentationDuration", &self.mediaPresentationDuration)
+            .field("periods", &self.periods.len())
+            .field("locations", &self.locations.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MPD {
+    /// A human-readable one-line summary of the manifest, such as
+    /// `MPD[static, 2 periods, 3600s, 4 adaptations per period]`.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.mpdtype.clone().unwrap_or_else(|| String::from("static"))];
+        parts.push(match self.periods.len() {
+            1 => String::from("1 period"),
+            n => format!("{n} periods"),
+        });
+        if let Some(d) = self.mediaPresentationDuration {
+            parts.push(format!("{}s", d.as_secs_f64()));
+        }
+        let counts: Vec<usize> = self.periods.iter().map(|p| p.adaptations.len()).collect();
+        let min = counts.iter().min();
+        let max = counts.iter().max();
+        match (min, max) {
+            (Some(1), Some(1)) => parts.push(String::from("1 adaptation per period")),
+            (Some(min), Some(max)) if min == max => parts.push(format!("{min} adaptations per period")),
+            (Some(min), Some(max)) => parts.push(format!("{min}-{max} adaptations per period")),
+            _ => (),
+        }
+        format!("MPD[{}]", parts.join(", "))
+    }
+
+    /// The profiles to which the manifest conforms, as declared by the `MPD@profiles` attribute.
+    pub fn profiles(&self) -> Vec<Profile> {
+        self.profiles.as_deref()
+            .map(|p| p.split(',')
+                 .filter(|u| !u.trim().is_empty())
+                 .map(Profile::from_urn)
+                 .collect())
+            .unwrap_or_default()
+    }
+}
+
+
 /// Parse an MPD manifest, provided as an XML string, returning an `MPD` node.
 pub fn parse(xml: &str) -> Result<MPD, DashMpdError> {
     let mpd: Result<MPD, quick_xml::DeError> = quick_xml::de::from_str(xml);
     match mpd {
-        Ok(mpd) => Ok(mpd),
+        Ok(mut mpd) => {
+            drop_empty_base_urls(&mut mpd);
+            Ok(mpd)
+        },
         Err(e) => Err(DashMpdError::Parsing(e.to_string())),
     }
 }
 
+// A BaseURL element containing only whitespace doesn't specify a URL (joining it with the
+// enclosing BaseURL would be pointless at best), so we ignore it. Elements fetched using XLink are
+// parsed separately from the manifest, so the fetch module also calls the functions below for them.
+fn drop_empty_base_urls(mpd: &mut MPD) {
+    mpd.base_url.retain(|bu| !bu.base.is_empty());
+    mpd.periods.iter_mut().for_each(drop_empty_period_base_urls);
+}
+
+pub(crate) fn drop_empty_period_base_urls(period: &mut Period) {
+    period.BaseURL.retain(|bu| !bu.base.is_empty());
+    period.adaptations.iter_mut().for_each(drop_empty_adaptation_base_urls);
+}
+
+pub(crate) fn drop_empty_adaptation_base_urls(adaptation: &mut AdaptationSet) {
+    adaptation.BaseURL.retain(|bu| !bu.base.is_empty());
+    adaptation.representations.iter_mut().for_each(drop_empty_representation_base_urls);
+}
+
+pub(crate) fn drop_empty_representation_base_urls(representation: &mut Representation) {
+    representation.BaseURL.retain(|bu| !bu.base.is_empty());
+}
+
+
+/// Parse a byte range specifier of the form "45-67", as used in the `SegmentURL@mediaRange`,
+/// `Initialization@range` and `SegmentBase@indexRange` attributes. Returns the positions of the
+/// first and last octets in the range (both inclusive, as in an HTTP Range request).
+///
+/// Open-ended ranges such as "45-", negative values and ranges whose end precedes their start are
+/// rejected.
+pub fn parse_byte_range(range: &str) -> Result<(u64, u64), DashMpdError> {
+    let (first, last) = range.trim().split_once('-')
+        .ok_or_else(|| DashMpdError::Parsing(
+            format!("invalid byte range {range:?}: expecting the form first-last")))?;
+    if first.is_empty() {
+        return Err(DashMpdError::Parsing(
+            format!("invalid byte range {range:?}: missing start (negative values are not allowed)")));
+    }
+    if last.is_empty() {
+        return Err(DashMpdError::Parsing(
+            format!("invalid byte range {range:?}: open-ended ranges are not supported")));
+    }
+    if last.starts_with('-') {
+        return Err(DashMpdError::Parsing(
+            format!("invalid byte range {range:?}: negative values are not allowed")));
+    }
+    let start: u64 = first.parse()
+        .map_err(|_| DashMpdError::Parsing(format!("invalid start for byte range {range:?}")))?;
+    let end: u64 = last.parse()
+        .map_err(|_| DashMpdError::Parsing(format!("invalid end for byte range {range:?}")))?;
+    if end < start {
+        return Err(DashMpdError::Parsing(
+            format!("invalid byte range {range:?}: end precedes start")));
+    }
+    Ok((start, end))
+}
+
+
+// From https://dashif.org/docs/DASH-IF-IOP-v4.3.pdf:
+// "For the avoidance of doubt, only %0[width]d is permitted and no other identifiers. The reason
+// is that such a string replacement can be easily implemented without requiring a specific library."
+//
+// Instead of pulling in C printf() or a reimplementation such as the printf_compat crate, we reimplement
+// this functionality directly.
+//
+// Example template: "$RepresentationID$/$Number%06d$.m4s"
+/// Resolve a `SegmentTemplate` URL template such as `"$RepresentationID$/$Number%06d$.m4s"`,
+/// substituting the values in `params` for the identifiers `RepresentationID`, `Number`, `Time` and
+/// `Bandwidth` (keys of `params` are the identifier names), and replacing the escape `$$` by a
+/// single `$`. Identifiers for which `params` contains no value, and any unrecognized identifiers,
+/// are left unreplaced.
+pub fn resolve_url_template(template: &str, params: &HashMap<&str, String>) -> String {
+    substitute_url_template(template, params, true)
+}
+
+// Resolve the identifiers in `template` for which `params` contains a value, as for
+// resolve_url_template, but leave `$$` escapes in place so that the result can be resolved again
+// with the remaining identifiers (such as $Number$ and $Time$ for each segment).
+#[cfg(feature = "fetch")]
+pub(crate) fn resolve_url_template_partial(template: &str, params: &HashMap<&str, String>) -> String {
+    substitute_url_template(template, params, false)
+}
+
+fn substitute_url_template(template: &str, params: &HashMap<&str, String>, unescape: bool) -> String {
+    // Fast path for templates which have already been fully resolved.
+    if !template.contains('$') {
+        return template.to_string();
+    }
+    URL_TEMPLATE_IDENT_RE.replace_all(template, |cap: &regex::Captures| {
+        let Some(ident) = cap.get(1) else {
+            // the escape sequence $$
+            return String::from(if unescape { "$" } else { "$$" });
+        };
+        match params.get(ident.as_str()) {
+            Some(value) => match cap.get(2) {
+                // a format tag such as $Number%06d$
+                Some(width) => {
+                    let width: usize = width.as_str().parse().unwrap();
+                    format!("{value:0>width$}")
+                },
+                None => value.to_string(),
+            },
+            None => cap[0].to_string(),
+        }
+    }).into_owned()
+}
+
+// Matches the identifiers in a SegmentTemplate URL template, either simple ($Number$) or with a
+// format tag ($Number%06d$), and the escaped dollar sign $$. Compiled once, because templates are
+// resolved for each media segment.
+static URL_TEMPLATE_IDENT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\$(RepresentationID|Number|Time|Bandwidth)(?:%0(\d)d)?\$|\$\$").unwrap()
+});
+
+const URL_TEMPLATE_IDENTIFIERS: [&str; 4] = ["RepresentationID", "Number", "Time", "Bandwidth"];
+
+/// Resolve a `SegmentTemplate` URL template as for `resolve_url_template`, but return an error if the
+/// template contains an unrecognized `$Identifier$`, an identifier with a format tag other than
+/// `%0[width]d`, or an unterminated identifier, rather than leaving it unreplaced.
+pub fn resolve_url_template_strict(
+    template: &str,
+    params: &HashMap<&str, String>) -> Result<String, DashMpdError>
+{
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        let after = &rest[start + 1..];
+        let end = after.find('$')
+            .ok_or_else(|| DashMpdError::Parsing(
+                format!("unterminated identifier in URL template {template:?}")))?;
+        let ident = &after[..end];
+        // "$$" is an escaped dollar sign
+        if !ident.is_empty() {
+            let (name, format) = match ident.split_once('%') {
+                Some((name, format)) => (name, Some(format)),
+                None => (ident, None),
+            };
+            if !URL_TEMPLATE_IDENTIFIERS.contains(&name) {
+                return Err(DashMpdError::Parsing(
+                    format!("unknown identifier ${ident}$ in URL template {template:?}")));
+            }
+            if let Some(fmt) = format {
+                let valid = fmt.len() == 3 &&
+                    fmt.starts_with('0') &&
+                    fmt.ends_with('d') &&
+                    fmt.as_bytes()[1].is_ascii_digit();
+                if !valid {
+                    return Err(DashMpdError::Parsing(
+                        format!("unsupported format tag in ${ident}$ in URL template {template:?}")));
+                }
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    Ok(resolve_url_template(template, params))
+}
+
 
 /// Returns `true` if this AdaptationSet contains audio content.
 ///
@@ -922,9 +1496,176 @@ pub fn is_video_adaptation(a: &&AdaptationSet) -> bool {
     false
 }
 
+/// Returns `true` if this AdaptationSet contains both audio and video content multiplexed in the
+/// same segments, as declared by its `ContentComponent` nodes.
+pub fn is_muxed_adaptation(a: &&AdaptationSet) -> bool {
+    let has_component = |ct: &str| a.ContentComponent.iter().any(|cc| cc.contentType.as_deref() == Some(ct));
+    has_component("audio") && has_component("video")
+}
+
+/// Returns `true` if this AdaptationSet contains audio description for the visually impaired, as
+/// signalled by an `Accessibility` descriptor with scheme `urn:tva:metadata:cs:AudioPurposeCS:2007`
+/// and value `1`.
+pub fn is_audio_description(a: &AdaptationSet) -> bool {
+    a.Accessibility.iter().any(|acc| {
+        acc.schemeIdUri.as_deref() == Some("urn:tva:metadata:cs:AudioPurposeCS:2007") &&
+            acc.value.as_deref() == Some("1")
+    })
+}
+
+/// Returns `true` if this AdaptationSet contains subtitles or captions.
+///
+/// It contains subtitles if the `contentType` attribute is `text`, or the `mimeType` attribute is
+/// `text/vtt` or `application/ttml+xml`, or if one of its child `Representation` nodes has such a
+/// `contentType` or `mimeType` attribute, or the `codecs` attribute is `wvtt` or `stpp` (WebVTT or
+/// TTML subtitles in ISO-BMFF fragments).
+pub fn is_subtitle_adaptation(a: &&AdaptationSet) -> bool {
+    fn text_mimetype_p(mimetype: &str) -> bool {
+        mimetype.eq("text/vtt") || mimetype.eq("application/ttml+xml")
+    }
+    fn text_codecs_p(codecs: &str) -> bool {
+        codecs.eq("wvtt") || codecs.starts_with("stpp")
+    }
+    if let Some(ct) = &a.contentType {
+        if ct == "text" {
+            return true;
+        }
+    }
+    if matches!(a.mimeType.as_deref(), Some(mt) if text_mimetype_p(mt)) ||
+        matches!(a.codecs.as_deref(), Some(c) if text_codecs_p(c)) {
+        return true;
+    }
+    for r in a.representations.iter() {
+        if let Some(ct) = &r.contentType {
+            if ct == "text" {
+                return true;
+            }
+        }
+        if matches!(r.mimeType.as_deref(), Some(mt) if text_mimetype_p(mt)) ||
+            matches!(r.codecs.as_deref(), Some(c) if text_codecs_p(c)) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns the delay behind the live edge at which a client should start playing a live (dynamic)
+/// stream, as suggested by the `MPD@suggestedPresentationDelay` attribute. This determines which
+/// segments should be requested first, and is particularly important for low-latency streams.
+/// Returns `None` for static manifests or if the attribute is not present. When downloading a
+/// dynamic manifest, the `fetch` module stops at this delay behind the live edge.
+pub fn suggested_delay(mpd: &MPD) -> Option<Duration> {
+    if mpd.mpdtype.as_deref() == Some("dynamic") {
+        mpd.suggestedPresentationDelay
+    } else {
+        None
+    }
+}
+
+/// The window of media that is available from a live (dynamic) stream, expressed as offsets from
+/// the `MPD@availabilityStartTime`. Segments that start before `earliest` have left the server's
+/// time-shift buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveWindow {
+    pub earliest: Duration,
+    pub latest: Duration,
+}
+
+impl LiveWindow {
+    /// Returns `true` if a segment starting at `start` (an offset from the `MPD@availabilityStartTime`)
+    /// is within the window.
+    pub fn contains(&self, start: Duration) -> bool {
+        self.earliest <= start && start <= self.latest
+    }
+}
+
+/// Returns the window of media available from a live (dynamic) stream at time `now`: the live edge
+/// is `now` minus the `MPD@availabilityStartTime`, and the earliest available media is the live
+/// edge minus the `MPD@timeShiftBufferDepth` (all media since the availabilityStartTime is
+/// available if this attribute is absent). Returns `None` for static manifests, if the
+/// availabilityStartTime is missing, or if `now` is before the availabilityStartTime.
+pub fn live_window(mpd: &MPD, now: XsDatetime) -> Option<LiveWindow> {
+    if mpd.mpdtype.as_deref() != Some("dynamic") {
+        return None;
+    }
+    let latest = (now - mpd.availabilityStartTime?).to_std().ok()?;
+    let earliest = match mpd.timeShiftBufferDepth {
+        Some(depth) => latest.saturating_sub(depth),
+        None => Duration::ZERO,
+    };
+    Some(LiveWindow { earliest, latest })
+}
+
+/// Returns the number of audio channels specified by an `AudioChannelConfiguration` element, for
+/// the channel configuration schemes defined by MPEG-DASH (`value` is the number of channels),
+/// MPEG CICP (`value` is a ChannelConfiguration index as defined in ISO/IEC 23091-3) and Dolby
+/// (`value` is a hexadecimal channel mask). Returns `None` for other schemes and malformed values.
+pub fn audio_channel_count(acc: &AudioChannelConfiguration) -> Option<u8> {
+    let value = acc.value.as_deref()?.trim();
+    match acc.schemeIdUri.as_deref()? {
+        "urn:mpeg:dash:23003:3:audio_channel_configuration:2011" => value.parse::<u8>().ok(),
+        "urn:mpeg:mpegB:cicp:ChannelConfiguration" => {
+            // Number of channels for each ChannelConfiguration index (0 means unspecified, 8 is
+            // reserved).
+            const CICP_CHANNELS: [u8; 21] = [0, 1, 2, 3, 4, 5, 6, 8, 0, 3, 4, 7, 8, 24, 8, 12, 10, 12, 14, 12, 14];
+            let index = value.parse::<usize>().ok()?;
+            CICP_CHANNELS.get(index).copied().filter(|c| *c > 0)
+        },
+        "tag:dolby.com,2014:dash:audio_channel_configuration:2011" |
+        "tag:dolby.com,2015:dash:audio_channel_configuration:2015" |
+        "urn:dolby:dash:audio_channel_configuration:2011" => {
+            let mask = u16::from_str_radix(value, 16).ok()?;
+            // Bits which designate a pair of channels (Lc/Rc, Lrs/Rrs, Lsd/Rsd, Lw/Rw, Vhl/Vhr, Lts/Rts)
+            const PAIRS: u16 = 0x0674;
+            let count = mask.count_ones() + (mask & PAIRS).count_ones();
+            u8::try_from(count).ok().filter(|c| *c > 0)
+        },
+        _ => None,
+    }
+}
+
+/// Returns the audio channel configurations of the audio Representations in the manifest, as a list
+/// of (number of channels, Representation id) pairs. The `AudioChannelConfiguration` of a
+/// Representation overrides that of its AdaptationSet. Representations whose number of channels
+/// can't be determined are not included.
+pub fn list_audio_channel_configs(mpd: &MPD) -> Vec<(u8, String)> {
+    let mut configs = Vec::new();
+    for period in &mpd.periods {
+        for a in period.adaptations.iter().filter(is_audio_adaptation) {
+            for r in &a.representations {
+                let acc = r.AudioChannelConfiguration.as_ref().or(a.AudioChannelConfiguration.as_ref());
+                if let Some(channels) = acc.and_then(audio_channel_count) {
+                    configs.push((channels, r.id.clone().unwrap_or_default()));
+                }
+            }
+        }
+    }
+    configs
+}
+
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_audio_channel_count() {
+        use super::{AudioChannelConfiguration, audio_channel_count};
+
+        let acc = |scheme: &str, value: &str| AudioChannelConfiguration {
+            schemeIdUri: Some(scheme.to_string()),
+            value: Some(value.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(audio_channel_count(&acc("urn:mpeg:dash:23003:3:audio_channel_configuration:2011", "2")), Some(2));
+        assert_eq!(audio_channel_count(&acc("urn:mpeg:dash:23003:3:audio_channel_configuration:2011", "x")), None);
+        assert_eq!(audio_channel_count(&acc("urn:mpeg:mpegB:cicp:ChannelConfiguration", "6")), Some(6));
+        assert_eq!(audio_channel_count(&acc("urn:mpeg:mpegB:cicp:ChannelConfiguration", "7")), Some(8));
+        assert_eq!(audio_channel_count(&acc("urn:mpeg:mpegB:cicp:ChannelConfiguration", "99")), None);
+        assert_eq!(audio_channel_count(&acc("tag:dolby.com,2014:dash:audio_channel_configuration:2011", "F801")), Some(6));
+        assert_eq!(audio_channel_count(&acc("tag:dolby.com,2014:dash:audio_channel_configuration:2011", "A000")), Some(2));
+        assert_eq!(audio_channel_count(&acc("tag:dolby.com,2014:dash:audio_channel_configuration:2011", "FA01")), Some(8));
+        assert_eq!(audio_channel_count(&acc("urn:example:unknown", "2")), None);
+    }
+
     #[test]
     fn test_parse_xs_duration() {
         use std::time::Duration;
@@ -968,4 +1709,193 @@ mod tests {
         // assert_eq!(parse_xs_duration("PT0.5H1S").ok(), Some(Duration::new(30*60+1, 0)));
         // assert_eq!(parse_xs_duration("P0001-02-03T04:05:06").ok(), Some(Duration::new(36993906, 0)));
     }
+
+    #[test]
+    fn test_resolve_url_template() {
+        use std::collections::HashMap;
+        use super::resolve_url_template;
+
+        assert_eq!(resolve_url_template("AA$Time$BB", &HashMap::from([("Time", "ZZZ".to_string())])),
+                   "AAZZZBB");
+        assert_eq!(resolve_url_template("AA$Number%06d$BB", &HashMap::from([("Number", "42".to_string())])),
+                   "AA000042BB");
+        let dict = HashMap::from([("RepresentationID", "640x480".to_string()),
+                                  ("Number", "42".to_string()),
+                                  ("Time", "ZZZ".to_string())]);
+        assert_eq!(resolve_url_template("AA/$RepresentationID$/segment-$Number%05d$.mp4", &dict),
+                   "AA/640x480/segment-00042.mp4");
+        assert_eq!(resolve_url_template("AA/$SubNumber$/$Number$.mp4", &dict),
+                   "AA/$SubNumber$/42.mp4");
+        assert_eq!(resolve_url_template("$Number%03d$/$Number%04d$-$Number$", &dict), "042/0042-42");
+        assert_eq!(resolve_url_template("$Bandwidth%06d$/$Time$", &dict), "$Bandwidth%06d$/ZZZ");
+        assert_eq!(resolve_url_template("no-identifiers.mp4", &dict), "no-identifiers.mp4");
+        assert_eq!(resolve_url_template("cost$$$Number$$$.mp4", &dict), "cost$42$.mp4");
+    }
+
+    #[test]
+    #[cfg(feature = "fetch")]
+    fn test_resolve_url_template_partial() {
+        use std::collections::HashMap;
+        use super::{resolve_url_template, resolve_url_template_partial};
+
+        let dict = HashMap::from([("RepresentationID", "640x480".to_string())]);
+        let partial = resolve_url_template_partial("$RepresentationID$/$$Number$$-$Number$", &dict);
+        assert_eq!(partial, "640x480/$$Number$$-$Number$");
+        let segment = HashMap::from([("Number", "42".to_string())]);
+        assert_eq!(resolve_url_template(&partial, &segment), "640x480/$Number$-42");
+    }
+
+    #[test]
+    fn test_resolve_url_template_strict() {
+        use std::collections::HashMap;
+        use super::{resolve_url_template_strict, DashMpdError};
+
+        let dict = HashMap::from([("RepresentationID", "640x480".to_string()),
+                                  ("Number", "42".to_string()),
+                                  ("Bandwidth", "128000".to_string())]);
+        assert_eq!(resolve_url_template_strict("$RepresentationID$/$Number%05d$.m4s", &dict).ok(),
+                   Some(String::from("640x480/00042.m4s")));
+        assert_eq!(resolve_url_template_strict("$Bandwidth$/init.mp4", &dict).ok(),
+                   Some(String::from("128000/init.mp4")));
+        assert_eq!(resolve_url_template_strict("cost$$/$Number$.m4s", &dict).ok(),
+                   Some(String::from("cost$/42.m4s")));
+        for bad in ["$SubNumber$/$Number$.m4s",
+                    "$representationid$/$Number$.m4s",
+                    "$Number%6d$.m4s",
+                    "$Number%05x$.m4s",
+                    "$Number.m4s"] {
+            match resolve_url_template_strict(bad, &dict) {
+                Err(DashMpdError::Parsing(msg)) => assert!(msg.contains(&format!("{bad:?}"))),
+                other => panic!("unexpected result for {bad:?}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_byte_range() {
+        use super::{parse_byte_range, DashMpdError};
+
+        assert_eq!(parse_byte_range("45-67").ok(), Some((45, 67)));
+        assert_eq!(parse_byte_range("0-0").ok(), Some((0, 0)));
+        assert_eq!(parse_byte_range(" 100-4000 ").ok(), Some((100, 4000)));
+        for bad in ["", "45", "0-", "-500", "-5-10", "5--3", "67-45", "a-b", "1.5-3"] {
+            match parse_byte_range(bad) {
+                Err(DashMpdError::Parsing(msg)) => assert!(msg.contains(&format!("{bad:?}"))),
+                other => panic!("unexpected result for {bad:?}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_base_url_whitespace() {
+        use super::parse;
+
+        let xml = r#"<MPD type="static">
+          <BaseURL>
+            https://cdn.example.com/content/
+          </BaseURL>
+          <Period>
+            <BaseURL>   </BaseURL>
+            <BaseURL>	//cdn2.example.com/path/
+</BaseURL>
+            <AdaptationSet><BaseURL></BaseURL><Representation id="1"><BaseURL> video/ </BaseURL></Representation></AdaptationSet>
+          </Period>
+        </MPD>"#;
+        let mpd = parse(xml).unwrap();
+        assert_eq!(mpd.base_url[0].base, "https://cdn.example.com/content/");
+        let period = &mpd.periods[0];
+        assert_eq!(period.BaseURL.len(), 1);
+        assert_eq!(period.BaseURL[0].base, "//cdn2.example.com/path/");
+        assert!(period.adaptations[0].BaseURL.is_empty());
+        assert_eq!(period.adaptations[0].representations[0].BaseURL[0].base, "video/");
+    }
+
+    #[test]
+    fn test_live_window() {
+        use std::time::Duration;
+        use super::{parse, live_window, LiveWindow, XsDatetime};
+
+        let xml = r#"<MPD type="dynamic" availabilityStartTime="2023-01-01T00:00:00Z" timeShiftBufferDepth="PT30S"><Period/></MPD>"#;
+        let mpd = parse(xml).unwrap();
+        assert_eq!(mpd.timeShiftBufferDepth, Some(Duration::new(30, 0)));
+        let now = "2023-01-01T00:10:00Z".parse::<XsDatetime>().unwrap();
+        let window = live_window(&mpd, now).unwrap();
+        assert_eq!(window, LiveWindow { earliest: Duration::new(570, 0), latest: Duration::new(600, 0) });
+        assert!(window.contains(Duration::new(580, 0)));
+        assert!(!window.contains(Duration::new(500, 0)));
+        // The time-shift buffer is longer than the time since the start of the stream
+        let now = "2023-01-01T00:00:10Z".parse::<XsDatetime>().unwrap();
+        assert_eq!(live_window(&mpd, now).unwrap().earliest, Duration::ZERO);
+        let now = "2022-12-31T00:00:00Z".parse::<XsDatetime>().unwrap();
+        assert!(live_window(&mpd, now).is_none());
+        let xml = r#"<MPD type="dynamic" availabilityStartTime="2023-01-01T00:00:00Z"><Period/></MPD>"#;
+        let now = "2023-01-01T00:10:00Z".parse::<XsDatetime>().unwrap();
+        assert_eq!(live_window(&parse(xml).unwrap(), now).unwrap().earliest, Duration::ZERO);
+        let xml = r#"<MPD type="static" timeShiftBufferDepth="PT30S"><Period/></MPD>"#;
+        assert!(live_window(&parse(xml).unwrap(), now).is_none());
+    }
+
+    #[test]
+    fn test_is_subtitle_adaptation() {
+        use super::{is_audio_adaptation, is_subtitle_adaptation, AdaptationSet, Representation};
+
+        let with_mimetype = |mt: &str| AdaptationSet { mimeType: Some(mt.to_string()), ..Default::default() };
+        assert!(is_subtitle_adaptation(&&with_mimetype("text/vtt")));
+        assert!(is_subtitle_adaptation(&&with_mimetype("application/ttml+xml")));
+        assert!(!is_subtitle_adaptation(&&with_mimetype("audio/mp4")));
+        let text = AdaptationSet { contentType: Some("text".to_string()), ..Default::default() };
+        assert!(is_subtitle_adaptation(&&text));
+        assert!(!is_audio_adaptation(&&text));
+        let stpp = AdaptationSet {
+            mimeType: Some("application/mp4".to_string()),
+            representations: vec![Representation { codecs: Some("stpp.ttml.im1t".to_string()), ..Default::default() }],
+            ..Default::default()
+        };
+        assert!(is_subtitle_adaptation(&&stpp));
+    }
+
+    #[test]
+    fn test_spatial_relationship() {
+        use super::{AdaptationSet, Period, SpatialRelationship, SupplementalProperty};
+
+        let srd = SpatialRelationship::from_value("1, 960, 0, 960, 540, 1920, 1080, 2").unwrap();
+        assert_eq!(srd.object_x, 960);
+        assert_eq!(srd.total_height, Some(1080));
+        assert_eq!(srd.spatial_set_id, Some(2));
+        assert!(!srd.covers_full_frame());
+        assert!(SpatialRelationship::from_value("1,0,0,1920,1080,1920,1080").unwrap().covers_full_frame());
+        assert!(!SpatialRelationship::from_value("1,0,0,1,1").unwrap().covers_full_frame());
+        assert!(SpatialRelationship::from_value("1,0,0,1,1,2").is_none());
+        assert!(SpatialRelationship::from_value("1,0,0,one,1").is_none());
+
+        let tile = AdaptationSet {
+            supplemental_property: vec![SupplementalProperty {
+                schemeIdUri: String::from("urn:mpeg:dash:srd:2014"),
+                value: Some(String::from("0,0,540,960,540,1920,1080")),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(tile.spatial_relationship().map(|srd| srd.object_y), Some(540));
+        assert!(AdaptationSet::default().spatial_relationship().is_none());
+
+        // The size of the reference space is inherited from an SRD with the same source_id.
+        let srd_adaptation = |value: &str| AdaptationSet {
+            supplemental_property: vec![SupplementalProperty {
+                schemeIdUri: String::from("urn:mpeg:dash:srd:2014"),
+                value: Some(String::from(value)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let period = Period {
+            adaptations: vec![tile, srd_adaptation("1,0,0,3840,2160,3840,2160"), srd_adaptation("0,0,0,960,540")],
+            ..Default::default()
+        };
+        let srd = period.spatial_relationship(&period.adaptations[2]).unwrap();
+        assert_eq!((srd.total_width, srd.total_height), (Some(1920), Some(1080)));
+        assert!(!srd.covers_full_frame());
+        let orphan = srd_adaptation("2,0,0,960,540");
+        assert_eq!(period.spatial_relationship(&orphan).and_then(|srd| srd.total_width), None);
+    }
 }
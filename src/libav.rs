@@ -20,6 +20,7 @@
 
 use std::cmp::{min, max};
 use std::fs::File;
+use std::path::Path;
 use ac_ffmpeg::codec::CodecParameters;
 use ac_ffmpeg::packet::Packet;
 use ac_ffmpeg::time::Timestamp;
@@ -29,7 +30,8 @@ use ac_ffmpeg::format::demuxer::DemuxerWithStreamInfo;
 use ac_ffmpeg::format::muxer::Muxer;
 use ac_ffmpeg::format::muxer::OutputFormat;
 use crate::DashMpdError;
-use crate::fetch::DashDownloader;
+use crate::fetch::{MuxMeta, MuxOptions};
+use crate::language::iso639_2_language;
 
 
 
@@ -44,17 +46,25 @@ fn libav_open_input(path: &str) -> Result<DemuxerWithStreamInfo<File>, DashMpdEr
         .map_err(|(_, _e)| DashMpdError::Muxing(String::from("building libav demuxer")))
 }
 
-fn libav_open_output(path: &str, elementary_streams: &[CodecParameters]) -> Result<Muxer<File>, DashMpdError> {
+// Open the output file with a muxer for the elementary streams, each of which is tagged with its
+// metadata (such as its language), as (key, value) pairs.
+fn libav_open_output(
+    path: &str,
+    elementary_streams: &[(CodecParameters, Vec<(&str, String)>)]) -> Result<Muxer<File>, DashMpdError>
+{
     let output_format = OutputFormat::guess_from_file_name(path)
         .or_else(|| OutputFormat::find_by_name("mp4"))
         .ok_or_else(|| DashMpdError::Muxing(String::from("guessing libav output format")))?;
     let output = File::create(path)
-        .map_err(|e| DashMpdError::Io(e, String::from("creating output file")))?;
+        .map_err(|e| DashMpdError::io(e, "creating output file"))?;
     let io = IO::from_seekable_write_stream(output);
     let mut muxer_builder = Muxer::builder();
-    for codec_parameters in elementary_streams {
-        muxer_builder.add_stream(codec_parameters)
+    for (codec_parameters, metadata) in elementary_streams {
+        let index = muxer_builder.add_stream(codec_parameters)
             .map_err(|_| DashMpdError::Muxing(String::from("adding libav stream to muxer")))?;
+        for (key, value) in metadata {
+            muxer_builder.streams_mut()[index].set_metadata(key, value);
+        }
     }
     muxer_builder
         // .interleaved(true)
@@ -72,9 +82,11 @@ fn has_invalid_timestamps(p: &Packet, last_dts: Timestamp) -> bool {
 
 
 pub fn mux_audio_video(
-    downloader: &DashDownloader,
+    _opts: &MuxOptions,
     audio_path: &str,
-    video_path: &str) -> Result<(), DashMpdError> {
+    video_path: &str,
+    output_path: &Path,
+    meta: &MuxMeta) -> Result<(), DashMpdError> {
     ac_ffmpeg::set_log_callback(|_count, msg: &str| log::info!("ffmpeg: {}", msg));
     let mut video_demuxer = libav_open_input(video_path)
         .map_err(|_| DashMpdError::Muxing(String::from("opening input video stream")))?;
@@ -104,9 +116,17 @@ pub fn mux_audio_video(
         })
         .ok_or_else(|| DashMpdError::Muxing(String::from("finding libav audio codec")))?;
 
-    let out = &downloader.output_path.as_ref().unwrap().to_str()
+    let out = &output_path.to_str()
         .ok_or_else(|| DashMpdError::Muxing(String::from("converting output path")))?;
-    let mut muxer = libav_open_output(out, &[video_codec, audio_codec])?;
+    // Tag the audio track with its language and title, as the ffmpeg and mkvmerge muxers do.
+    let mut audio_metadata = Vec::new();
+    if let Some(lang) = meta.audio_language.as_deref().and_then(iso639_2_language) {
+        audio_metadata.push(("language", lang));
+    }
+    if let Some(title) = &meta.audio_title {
+        audio_metadata.push(("title", title.clone()));
+    }
+    let mut muxer = libav_open_output(out, &[(video_codec, Vec::new()), (audio_codec, audio_metadata)])?;
     let mut last_dts: Timestamp = Timestamp::null();
 
     // wonder about memory consumption here, should we interleave the pushes?
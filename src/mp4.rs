@@ -0,0 +1,66 @@
+//! Reading the boxes of ISO-BMFF (MPEG-4) data, for the Segment Index and TTML subtitle parsers.
+//!
+//! Errors are short descriptions of the problem, to which the caller adds its context.
+
+
+pub(crate) fn read_u32(data: &[u8], pos: usize) -> Result<u32, &'static str> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or("truncated box")
+}
+
+pub(crate) fn read_u64(data: &[u8], pos: usize) -> Result<u64, &'static str> {
+    Ok(((read_u32(data, pos)? as u64) << 32) + read_u32(data, pos + 4)? as u64)
+}
+
+// An ISO-BMFF box: its four-character type and its payload (following the box header).
+pub(crate) struct Mp4Box<'a> {
+    pub boxtype: [u8; 4],
+    pub payload: &'a [u8],
+}
+
+// Iterates over the consecutive boxes in ISO-BMFF data. After an error (a truncated header or a
+// box size which is inconsistent with the data), the iteration stops.
+pub(crate) struct Boxes<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+pub(crate) fn boxes(data: &[u8]) -> Boxes<'_> {
+    Boxes { data, pos: 0 }
+}
+
+impl<'a> Boxes<'a> {
+    fn next_box(&mut self) -> Result<Mp4Box<'a>, &'static str> {
+        let (data, pos) = (self.data, self.pos);
+        let size = read_u32(data, pos)? as usize;
+        let boxtype = data.get(pos + 4..pos + 8)
+            .ok_or("truncated box header")?;
+        let boxtype = [boxtype[0], boxtype[1], boxtype[2], boxtype[3]];
+        let (header, size) = match size {
+            0 => (8, data.len() - pos),
+            1 => (16, read_u64(data, pos + 8)? as usize),
+            _ => (8, size),
+        };
+        if size < header || pos.checked_add(size).map_or(true, |end| end > data.len()) {
+            return Err("invalid box size");
+        }
+        self.pos += size;
+        Ok(Mp4Box { boxtype, payload: &data[pos + header..pos + size] })
+    }
+}
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = Result<Mp4Box<'a>, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let item = self.next_box();
+        if item.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(item)
+    }
+}
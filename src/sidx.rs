@@ -0,0 +1,147 @@
+//! Parsing of the Segment Index box of ISO-BMFF (MPEG-4) media.
+//!
+//! An index segment (referenced by the `SegmentTemplate@index` or `SegmentBase@indexRange`
+//! attributes of a DASH manifest) contains a Segment Index box (`sidx`), which describes the size
+//! and duration of each of the media segments (or subsegments) of a Representation.
+
+
+use crate::DashMpdError;
+use crate::mp4::{boxes, read_u32, read_u64};
+
+
+/// A reference from a Segment Index box to a media (sub)segment, or to another Segment Index box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SidxReference {
+    /// `true` if the reference is to another Segment Index box (a hierarchical index), rather than
+    /// to media content.
+    pub reference_type: bool,
+    /// The size of the referenced material, in octets.
+    pub referenced_size: u32,
+    /// The duration of the referenced (sub)segment, in units of the timescale of the index.
+    pub subsegment_duration: u32,
+}
+
+/// The content of a Segment Index box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentIndex {
+    pub timescale: u32,
+    /// The presentation time of the first referenced (sub)segment, in units of the timescale.
+    pub earliest_presentation_time: u64,
+    /// The distance in octets from the end of the Segment Index box to the first referenced
+    /// material.
+    pub first_offset: u64,
+    pub references: Vec<SidxReference>,
+}
+
+impl SegmentIndex {
+    /// Returns the start time and duration in seconds of each referenced (sub)segment.
+    pub fn timing(&self) -> Vec<(f64, f64)> {
+        let timescale = f64::from(self.timescale.max(1));
+        let mut time = self.earliest_presentation_time;
+        let mut timing = Vec::new();
+        for r in &self.references {
+            timing.push((time as f64 / timescale, f64::from(r.subsegment_duration) / timescale));
+            time += u64::from(r.subsegment_duration);
+        }
+        timing
+    }
+}
+
+
+fn sidx_error(why: &str) -> DashMpdError {
+    DashMpdError::Parsing(format!("parsing Segment Index box: {why}"))
+}
+
+// Parse the payload of a sidx box (following the box header).
+fn parse_sidx_payload(payload: &[u8]) -> Result<SegmentIndex, DashMpdError> {
+    let version = *payload.first()
+        .ok_or_else(|| sidx_error("truncated box"))?;
+    // Skip the version and flags, and the reference_ID
+    let timescale = read_u32(payload, 8).map_err(sidx_error)?;
+    let (earliest_presentation_time, first_offset, mut pos) = if version == 0 {
+        (read_u32(payload, 12).map_err(sidx_error)? as u64,
+         read_u32(payload, 16).map_err(sidx_error)? as u64,
+         20)
+    } else {
+        (read_u64(payload, 12).map_err(sidx_error)?, read_u64(payload, 20).map_err(sidx_error)?, 28)
+    };
+    // Skip 16 reserved bits
+    let reference_count = (read_u32(payload, pos).map_err(sidx_error)? & 0xFFFF) as usize;
+    pos += 4;
+    let mut references = Vec::with_capacity(reference_count);
+    for _ in 0..reference_count {
+        let word = read_u32(payload, pos).map_err(sidx_error)?;
+        references.push(SidxReference {
+            reference_type: word & 0x8000_0000 != 0,
+            referenced_size: word & 0x7FFF_FFFF,
+            subsegment_duration: read_u32(payload, pos + 4).map_err(sidx_error)?,
+        });
+        // Skip the SAP information
+        pos += 12;
+    }
+    Ok(SegmentIndex { timescale, earliest_presentation_time, first_offset, references })
+}
+
+/// Parse the first Segment Index box in ISO-BMFF data (such as an index segment, which may start
+/// with a Segment Type box).
+pub fn parse_sidx(data: &[u8]) -> Result<SegmentIndex, DashMpdError> {
+    for b in boxes(data) {
+        let b = b.map_err(sidx_error)?;
+        if &b.boxtype == b"sidx" {
+            return parse_sidx_payload(b.payload);
+        }
+    }
+    Err(sidx_error("no sidx box found"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    // Build a version 0 sidx box with the given (reference_type, referenced_size, duration) references.
+    fn sidx_box(timescale: u32, earliest: u32, references: &[(bool, u32, u32)]) -> Vec<u8> {
+        let mut payload = vec![0, 0, 0, 0];
+        payload.extend(1u32.to_be_bytes());
+        payload.extend(timescale.to_be_bytes());
+        payload.extend(earliest.to_be_bytes());
+        payload.extend(0u32.to_be_bytes());
+        payload.extend((references.len() as u32).to_be_bytes());
+        for (reftype, size, duration) in references {
+            let word = (u32::from(*reftype) << 31) | size;
+            payload.extend(word.to_be_bytes());
+            payload.extend(duration.to_be_bytes());
+            payload.extend(0x9000_0000u32.to_be_bytes());
+        }
+        let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(b"sidx");
+        b.extend(payload);
+        b
+    }
+
+    #[test]
+    fn test_parse_sidx() {
+        use super::{parse_sidx, SidxReference};
+
+        let mut data = vec![0, 0, 0, 16];
+        data.extend_from_slice(b"stypmsdh");
+        data.extend(0u32.to_be_bytes());
+        data.extend(sidx_box(1000, 500, &[(false, 1234, 2000), (false, 5678, 1500)]));
+        let sidx = parse_sidx(&data).unwrap();
+        assert_eq!(sidx.timescale, 1000);
+        assert_eq!(sidx.earliest_presentation_time, 500);
+        assert_eq!(sidx.references.len(), 2);
+        assert_eq!(sidx.references[1],
+                   SidxReference { reference_type: false, referenced_size: 5678, subsegment_duration: 1500 });
+        assert_eq!(sidx.timing(), vec![(0.5, 2.0), (2.5, 1.5)]);
+
+        assert!(parse_sidx(b"").is_err());
+        assert!(parse_sidx(&data[..16]).is_err());
+        let truncated = sidx_box(1000, 0, &[(false, 1, 1)]);
+        assert!(parse_sidx(&truncated[..truncated.len() - 4]).is_err());
+        // A box size which overflows is rejected
+        let mut huge = data[..16].to_vec();
+        huge.extend([0, 0, 0, 1]);
+        huge.extend_from_slice(b"free");
+        huge.extend(u64::MAX.to_be_bytes());
+        assert!(parse_sidx(&huge).is_err());
+    }
+}
@@ -0,0 +1,446 @@
+//! Computing the media timeline of a Representation.
+//!
+//! The media segments of a Representation using `SegmentTemplate` addressing are described either
+//! by a `SegmentTimeline` (explicit start times and durations, with repetitions and possibly gaps)
+//! or by a `SegmentTemplate@duration` (segments of constant duration which cover the Period). This
+//! module computes the resulting start time and duration of each media segment, for example to
+//! build a scrub bar or a quality control report. The same computation determines which segments
+//! are downloaded by the `fetch` module.
+
+
+use std::time::Duration;
+use crate::{MPD, Period, AdaptationSet, Representation, SegmentTemplate, S, DashMpdError, parse_xs_duration};
+
+
+/// The timing of a media segment of a Representation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentTiming {
+    /// The number of the segment (the value of the `$Number$` template identifier).
+    pub number: u64,
+    /// The start time of the segment in timescale units (the value of the `$Time$` template
+    /// identifier), which includes the presentationTimeOffset.
+    pub time: i64,
+    /// The duration of the segment in timescale units.
+    pub duration: i64,
+    /// The number of timescale units per second.
+    pub timescale: u64,
+    /// The start time of the segment in seconds, relative to the start of the Period.
+    pub start_secs: f64,
+    /// The duration of the segment in seconds.
+    pub duration_secs: f64,
+}
+
+// The segment timings computed from a SegmentTemplate, with warnings concerning discontinuities in
+// its SegmentTimeline.
+pub(crate) struct Timeline {
+    pub(crate) segments: Vec<SegmentTiming>,
+    #[cfg_attr(not(feature = "fetch"), allow(dead_code))]
+    pub(crate) warnings: Vec<String>,
+}
+
+/// The default maximum number of media segments of a Representation.
+pub const MAX_SEGMENT_COUNT: u64 = 500_000;
+
+/// Compute the start time and duration of each media segment of Representation `rep` of
+/// AdaptationSet `adaptation` in the Period at index `period_index` of the manifest `mpd`. The
+/// duration of the Period is computed as for a download: its @duration attribute, or else the time
+/// until the start of the next Period or the end of the presentation. The SegmentTemplate of the
+/// Representation is merged with those of the AdaptationSet and the Period, and the media segments
+/// are described either by its SegmentTimeline or by its @duration attribute. A SegmentTimeline S
+/// element with a negative @r is repeated until the next S element or the end of the Period.
+///
+/// Returns an error for other addressing modes, whose segments are not described by the manifest
+/// (for instance SegmentTemplate@index addressing, where they are described by an index segment),
+/// and for Representations with more than `MAX_SEGMENT_COUNT` segments.
+///
+/// Example
+/// ```rust
+/// let xml = r#"<MPD mediaPresentationDuration="PT5S"><Period><AdaptationSet>
+///   <SegmentTemplate timescale="1000" duration="2000" media="$Number$.m4s"/>
+///   <Representation id="v1" bandwidth="500000"/>
+/// </AdaptationSet></Period></MPD>"#;
+/// let mpd = dash_mpd::parse(xml).unwrap();
+/// let adaptation = &mpd.periods[0].adaptations[0];
+/// let timings = dash_mpd::timeline::build(&adaptation.representations[0], adaptation, 0, &mpd)
+///     .unwrap();
+/// assert_eq!(timings.len(), 3);
+/// assert_eq!(timings[2].start_secs, 4.0);
+/// ```
+pub fn build(
+    rep: &Representation,
+    adaptation: &AdaptationSet,
+    period_index: usize,
+    mpd: &MPD) -> Result<Vec<SegmentTiming>, DashMpdError>
+{
+    let period = mpd.periods.get(period_index)
+        .ok_or_else(|| DashMpdError::Other(format!("manifest has no Period {period_index}")))?;
+    let adaptation_st = merge_segment_templates(period.SegmentTemplate.as_ref(), adaptation.SegmentTemplate.as_ref());
+    let st = merge_segment_templates(adaptation_st.as_ref(), rep.SegmentTemplate.as_ref())
+        .ok_or_else(|| DashMpdError::UnhandledMediaStream(
+            "Representation without a SegmentTemplate".to_string()))?;
+    let period_duration_secs = period_durations(mpd.mediaPresentationDuration, &mpd.periods)[period_index];
+    let id = rep.id.as_deref().unwrap_or_default();
+    Ok(template_timeline(&st, id, period_duration_secs, None, MAX_SEGMENT_COUNT)?.segments)
+}
+
+// The duration in seconds of each Period of a manifest whose MPD@mediaPresentationDuration is
+// `presentation_duration`. A Period without a @duration attribute lasts until the start of the next
+// Period, or for the last Period until the end of the presentation. The start of a Period without a
+// @start attribute is the end of the previous Period (the first Period starts at 0). When neither is
+// known, we fall back to the presentation duration (0 if unknown).
+pub(crate) fn period_durations(presentation_duration: Option<Duration>, periods: &[Period]) -> Vec<f64> {
+    let mut starts: Vec<Option<f64>> = Vec::with_capacity(periods.len());
+    for (i, period) in periods.iter().enumerate() {
+        let start = match period.start.as_deref() {
+            Some(s) => parse_xs_duration(s).ok().map(|d| d.as_secs_f64()),
+            None if i == 0 => Some(0.0),
+            None => starts[i - 1].zip(periods[i - 1].duration).map(|(s, d)| s + d.as_secs_f64()),
+        };
+        starts.push(start);
+    }
+    let presentation_secs = presentation_duration.map(|d| d.as_secs_f64());
+    periods.iter().enumerate().map(|(i, period)| {
+        if let Some(d) = period.duration {
+            return d.as_secs_f64();
+        }
+        let end = if i + 1 < periods.len() { starts[i + 1] } else { presentation_secs };
+        end.zip(starts[i])
+            .map(|(end, start)| end - start)
+            .filter(|secs| *secs > 0.0)
+            .or(presentation_secs)
+            .unwrap_or(0.0)
+    }).collect()
+}
+
+// The media segments described by the SegmentTimeline or @duration of a SegmentTemplate (which
+// has been merged with the SegmentTemplates of its parent elements) of Representation
+// `representation`. The repetition of an S element with a negative @r ends at the end of the Period,
+// or after `fallback` when the Period duration is unknown. An error if there are more than
+// `max_segments` segments, which would only be the case for a broken or malicious manifest (for
+// example with a tiny @duration or a huge Period duration).
+pub(crate) fn template_timeline(
+    st: &SegmentTemplate,
+    representation: &str,
+    period_duration_secs: f64,
+    fallback: Option<Duration>,
+    max_segments: u64) -> Result<Timeline, DashMpdError>
+{
+    let timescale = st.timescale.unwrap_or(1);
+    let start_number = st.startNumber.unwrap_or(1);
+    let pto = st.presentationTimeOffset.unwrap_or(0) as i64;
+    let mut segments = Vec::new();
+    let mut warnings = Vec::new();
+    let timing = |number: u64, time: i64, duration: i64| SegmentTiming {
+        number,
+        time,
+        duration,
+        timescale,
+        start_secs: (time - pto) as f64 / timescale as f64,
+        duration_secs: duration as f64 / timescale as f64,
+    };
+    if let Some(stl) = &st.SegmentTimeline {
+        // The start time and number of repetitions of each S element, computed before any segment
+        // is enumerated so that a pathological timeline is rejected without allocating.
+        let mut runs = Vec::with_capacity(stl.segments.len());
+        let mut total: u64 = 0;
+        // S@t gives the start time of the segment. When it is absent, the segment starts when the
+        // previous one ends, or at 0 for the first S element of the timeline.
+        let mut segment_time = 0;
+        for (i, s) in stl.segments.iter().enumerate() {
+            if let Some(t) = s.t {
+                if i > 0 {
+                    if let Some(msg) = timeline_discontinuity(segment_time, t, stl.segments[i - 1].d) {
+                        warnings.push(msg);
+                    }
+                }
+                segment_time = t;
+            }
+            let end_time = timeline_repeat_end(&stl.segments, i, segment_time, timescale, pto, period_duration_secs, fallback);
+            if matches!(s.r, Some(r) if r < 0) && end_time.is_none() {
+                log::warn!("Can't determine the end of SegmentTimeline S@r=-1 repetition (no duration in manifest)");
+            }
+            let count = repeat_count(s, segment_time, end_time);
+            runs.push((segment_time, count, s.d));
+            total = total.saturating_add(count);
+            segment_time = segment_time.saturating_add(s.d.saturating_mul(count.try_into().unwrap_or(i64::MAX)));
+        }
+        check_segment_count(representation, total, max_segments)?;
+        let mut number = start_number;
+        for (start, count, d) in runs {
+            let mut time = start;
+            for _ in 0..count {
+                segments.push(timing(number, time, d));
+                number += 1;
+                time += d;
+            }
+        }
+    } else if st.duration.is_some() {
+        let (d, timescale) = template_segment_duration(None, st)
+            .ok_or_else(|| DashMpdError::UnhandledMediaStream("invalid SegmentTemplate@duration".to_string()))?;
+        let segment_duration = d / timescale as f64;
+        let count = template_segment_count(period_duration_secs, d, timescale);
+        check_segment_count(representation, count, max_segments)?;
+        for i in 0..count {
+            let mut t = timing(start_number + i, pto + (i as f64 * d).round() as i64, d.round() as i64);
+            // computed in floating point, since (non-conformant) durations may be fractional
+            t.start_secs = i as f64 * segment_duration;
+            t.duration_secs = segment_duration;
+            segments.push(t);
+        }
+    } else {
+        return Err(DashMpdError::UnhandledMediaStream(
+            "SegmentTemplate without a SegmentTimeline or @duration".to_string()));
+    }
+    Ok(Timeline { segments, warnings })
+}
+
+// The number of segments described by an S element starting at time `start`: 1 + @r, or for a
+// negative @r, as many as needed to reach `end_time`. The repetitions are computed rather than
+// iterated, so that a bogus Period duration can't make us loop for a long time.
+fn repeat_count(s: &S, start: i64, end_time: Option<i64>) -> u64 {
+    match s.r {
+        Some(r) if r < 0 => match end_time {
+            Some(end) if s.d > 0 && end > start => end.abs_diff(start).div_ceil(s.d as u64),
+            _ => 1,
+        },
+        Some(r) => (r as u64).saturating_add(1),
+        None => 1,
+    }
+}
+
+fn check_segment_count(representation: &str, count: u64, max_segments: u64) -> Result<(), DashMpdError> {
+    if count > max_segments {
+        return Err(DashMpdError::UnhandledMediaStream(
+            format!("Representation {representation} has {count} media segments, more than the maximum of {max_segments}")));
+    }
+    Ok(())
+}
+
+// Compare the S@t of an S element with `expected`, the end time of the previous segment in the
+// SegmentTimeline, returning a warning message if the timeline is discontinuous. A gap longer than the
+// duration of the previous segment is reported as a structural gap (missing content) rather than as
+// timing jitter.
+pub(crate) fn timeline_discontinuity(expected: i64, t: i64, previous_duration: i64) -> Option<String> {
+    if t == expected {
+        None
+    } else if t > expected + previous_duration {
+        Some(format!("SegmentTimeline structural gap: expected t={expected}, got t={t} ({} timescale units missing)",
+                     t - expected))
+    } else {
+        Some(format!("SegmentTimeline discontinuity: expected t={expected}, got t={t}"))
+    }
+}
+
+// The time (in timescale units) at which the repetition of segments[i], an S element with a
+// negative @r, ends. As per the DASH specification, the duration "repeats until the start of the next
+// S element, the end of the Period or until the next MPD update". Media times are offset by the
+// presentationTimeOffset `pto`, so the end of the Period is at media time pto + its duration. The end
+// of the Period is unknown when neither MPD@mediaPresentationDuration nor Period@duration is
+// specified (which is common in "live-as-VOD" manifests); we then repeat for the `fallback` duration
+// from the start of the S element.
+pub(crate) fn timeline_repeat_end(
+    segments: &[S],
+    i: usize,
+    start: i64,
+    timescale: u64,
+    pto: i64,
+    period_duration_secs: f64,
+    fallback: Option<Duration>) -> Option<i64>
+{
+    if let Some(t) = segments.get(i + 1).and_then(|next| next.t) {
+        return Some(t);
+    }
+    if period_duration_secs > 0.0 {
+        return Some(pto.saturating_add((period_duration_secs * timescale as f64) as i64));
+    }
+    fallback.map(|d| start + (d.as_secs_f64() * timescale as f64) as i64)
+}
+
+
+// Merge a SegmentTemplate with the SegmentTemplate of its parent element (for example an
+// AdaptationSet-level template with a Period-level template). Attributes specified on the child
+// override those of the parent, and unspecified attributes are inherited from the parent.
+pub(crate) fn merge_segment_templates(
+    parent: Option<&SegmentTemplate>,
+    child: Option<&SegmentTemplate>) -> Option<SegmentTemplate>
+{
+    let (parent, child) = match (parent, child) {
+        (None, None) => return None,
+        (Some(p), None) => return Some(p.clone()),
+        (None, Some(c)) => return Some(c.clone()),
+        (Some(p), Some(c)) => (p, c),
+    };
+    Some(SegmentTemplate {
+        initialization: child.initialization.clone().or_else(|| parent.initialization.clone()),
+        media: child.media.clone().or_else(|| parent.media.clone()),
+        index: child.index.clone().or_else(|| parent.index.clone()),
+        SegmentTimeline: child.SegmentTimeline.clone().or_else(|| parent.SegmentTimeline.clone()),
+        startNumber: child.startNumber.or(parent.startNumber),
+        duration: child.duration.or(parent.duration),
+        timescale: child.timescale.or(parent.timescale),
+        eptDelta: child.eptDelta.or(parent.eptDelta),
+        presentationTimeOffset: child.presentationTimeOffset.or(parent.presentationTimeOffset),
+        bitstreamSwitching: child.bitstreamSwitching.or(parent.bitstreamSwitching),
+        availabilityTimeOffset: child.availabilityTimeOffset.or(parent.availabilityTimeOffset),
+        availabilityTimeComplete: child.availabilityTimeComplete.or(parent.availabilityTimeComplete),
+        FailoverContent: child.FailoverContent.clone().or_else(|| parent.FailoverContent.clone()),
+    })
+}
+
+// The duration of the segments described by a SegmentTemplate, as a (duration, timescale) pair
+// (the duration in seconds is duration / timescale). The @duration and @timescale attributes are
+// inherited independently from the AdaptationSet-level SegmentTemplate, and the duration is always
+// expressed in units of the effective timescale.
+pub(crate) fn template_segment_duration(adaptation_st: Option<&SegmentTemplate>, st: &SegmentTemplate) -> Option<(f64, u64)> {
+    let duration = st.duration.or_else(|| adaptation_st.and_then(|ast| ast.duration))?;
+    let timescale = st.timescale
+        .or_else(|| adaptation_st.and_then(|ast| ast.timescale))
+        .unwrap_or(1);
+    if timescale == 0 || duration <= 0.0 {
+        return None;
+    }
+    Some((duration, timescale))
+}
+
+// The number of segments of the specified duration (in timescale units) needed to cover a Period.
+// With large timescales (such as 10_000_000 for packagers that use 100ns units) the rounding errors
+// of floating point arithmetic can add or drop a segment at the end of the Period, so we convert
+// the Period duration to timescale units once (with explicit rounding) and use integer arithmetic.
+// Floating point arithmetic is only used for (non-conformant) fractional durations.
+pub(crate) fn template_segment_count(period_duration_secs: f64, duration: f64, timescale: u64) -> u64 {
+    let period_ticks = (period_duration_secs * timescale as f64).round();
+    if duration.fract() == 0.0 && duration < u64::MAX as f64 {
+        (period_ticks as u64).div_ceil(duration as u64)
+    } else {
+        (period_ticks / duration).ceil() as u64
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{build, SegmentTiming};
+    use crate::parse;
+
+    // The timings of the first Representation of a single-Period manifest, as (number, time,
+    // duration, start_secs, duration_secs) tuples.
+    fn timings(mpd_attrs: &str, adaptation: &str) -> Vec<(u64, i64, i64, f64, f64)> {
+        let xml = format!(r#"<MPD {mpd_attrs}><Period>{adaptation}</Period></MPD>"#);
+        let mpd = parse(&xml).unwrap();
+        let a = &mpd.periods[0].adaptations[0];
+        build(&a.representations[0], a, 0, &mpd).unwrap()
+            .into_iter()
+            .map(|t: SegmentTiming| (t.number, t.time, t.duration, t.start_secs, t.duration_secs))
+            .collect()
+    }
+
+    #[test]
+    fn test_period_durations() {
+        use std::time::Duration;
+        use crate::Period;
+        use super::period_durations;
+
+        let period = |start: Option<&str>, duration: Option<u64>| Period {
+            start: start.map(String::from),
+            duration: duration.map(Duration::from_secs),
+            ..Default::default()
+        };
+        let total = Some(Duration::from_secs(10));
+        assert_eq!(period_durations(total, &[period(None, None)]), vec![10.0]);
+        // Without @duration, a Period lasts until the next Period or the end of the presentation
+        assert_eq!(period_durations(total, &[period(Some("PT0S"), None), period(Some("PT4S"), None)]),
+                   vec![4.0, 6.0]);
+        // The start of a Period is the end of the previous Period, if it has a @duration
+        assert_eq!(period_durations(total, &[period(None, Some(3)), period(None, None)]), vec![3.0, 7.0]);
+        assert_eq!(period_durations(None, &[period(None, Some(3)), period(Some("PT3S"), None)]), vec![3.0, 0.0]);
+        // Unknown starts fall back to the presentation duration
+        assert_eq!(period_durations(total, &[period(None, None), period(None, None)]), vec![10.0, 10.0]);
+    }
+
+    #[test]
+    fn test_timeline_repeats_and_gaps() {
+        // startNumber and presentationTimeOffset, S@r repetition and a gap before the last S element
+        let t = timings(r#"mediaPresentationDuration="PT20S""#, r#"<AdaptationSet>
+          <Representation id="a" bandwidth="1">
+            <SegmentTemplate timescale="1000" startNumber="5" presentationTimeOffset="500" media="$Time$.m4s">
+              <SegmentTimeline><S t="500" d="2000" r="2"/><S d="1000"/><S t="9500" d="3000"/></SegmentTimeline>
+            </SegmentTemplate>
+          </Representation></AdaptationSet>"#);
+        assert_eq!(t, vec![(5, 500, 2000, 0.0, 2.0),
+                           (6, 2500, 2000, 2.0, 2.0),
+                           (7, 4500, 2000, 4.0, 2.0),
+                           (8, 6500, 1000, 6.0, 1.0),
+                           (9, 9500, 3000, 9.0, 3.0)]);
+    }
+
+    #[test]
+    fn test_timeline_negative_repeat() {
+        // S@r=-1 repeats until the next S@t, then until the end of the Period
+        let t = timings(r#"mediaPresentationDuration="PT60S""#, r#"<AdaptationSet>
+          <SegmentTemplate timescale="10" media="$Number$.m4s">
+            <SegmentTimeline><S t="0" d="20" r="-1"/><S t="50" d="30" r="-1"/></SegmentTimeline>
+          </SegmentTemplate>
+          <Representation id="a" bandwidth="1"/></AdaptationSet>"#);
+        let starts: Vec<f64> = t.iter().map(|t| t.3).collect();
+        assert_eq!(starts, vec![0.0, 2.0, 4.0, 5.0, 8.0, 11.0, 14.0, 17.0, 20.0, 23.0, 26.0, 29.0,
+                                32.0, 35.0, 38.0, 41.0, 44.0, 47.0, 50.0, 53.0, 56.0, 59.0]);
+        assert_eq!(t[0].0, 1);
+    }
+
+    #[test]
+    fn test_timeline_negative_repeat_offset() {
+        // The media times of a Period extracted from a long recording are offset by a large
+        // presentationTimeOffset, so S@r=-1 repeats until the media time pto + Period duration
+        let t = timings(r#"mediaPresentationDuration="PT10S""#, r#"<AdaptationSet>
+          <SegmentTemplate timescale="90000" presentationTimeOffset="324000000000" media="$Time$.m4s">
+            <SegmentTimeline><S t="324000000000" d="180000" r="-1"/></SegmentTimeline>
+          </SegmentTemplate>
+          <Representation id="a" bandwidth="1"/></AdaptationSet>"#);
+        assert_eq!(t.len(), 5);
+        assert_eq!(t[0], (1, 324000000000, 180000, 0.0, 2.0));
+        assert_eq!(t[4], (5, 324000720000, 180000, 8.0, 2.0));
+    }
+
+    #[test]
+    fn test_timeline_duration() {
+        // @duration inherited from the AdaptationSet, @timescale and @startNumber from the Representation
+        let t = timings(r#"mediaPresentationDuration="PT5S""#, r#"<AdaptationSet>
+          <SegmentTemplate duration="180000" presentationTimeOffset="90000" media="$Number$.m4s"/>
+          <Representation id="a" bandwidth="1"><SegmentTemplate timescale="90000" startNumber="0"/></Representation>
+          </AdaptationSet>"#);
+        assert_eq!(t, vec![(0, 90000, 180000, 0.0, 2.0),
+                           (1, 270000, 180000, 2.0, 2.0),
+                           (2, 450000, 180000, 4.0, 2.0)]);
+    }
+
+    #[test]
+    fn test_timeline_segment_count_limit() {
+        let build_err = |adaptation: &str| {
+            let xml = format!(r#"<MPD mediaPresentationDuration="P400D"><Period><AdaptationSet>
+              {adaptation}<Representation id="v1" bandwidth="1"/></AdaptationSet></Period></MPD>"#);
+            let mpd = parse(&xml).unwrap();
+            let a = &mpd.periods[0].adaptations[0];
+            build(&a.representations[0], a, 0, &mpd).unwrap_err().to_string()
+        };
+        // a tiny @duration
+        let msg = build_err(r#"<SegmentTemplate timescale="1000" duration="1" media="$Number$.m4s"/>"#);
+        assert!(msg.contains("Representation v1 has 34560000000 media segments, more than the maximum of 500000"), "{msg}");
+        // a negative @r repeated until the end of a huge Period, and a huge positive @r
+        let msg = build_err(r#"<SegmentTemplate timescale="1" media="$Number$.m4s">
+              <SegmentTimeline><S t="0" d="1" r="-1"/></SegmentTimeline></SegmentTemplate>"#);
+        assert!(msg.contains("has 34560000 media segments"), "{msg}");
+        let msg = build_err(r#"<SegmentTemplate timescale="1" media="$Number$.m4s">
+              <SegmentTimeline><S d="2" r="9223372036854775807"/></SegmentTimeline></SegmentTemplate>"#);
+        assert!(msg.contains("has 9223372036854775808 media segments"), "{msg}");
+    }
+
+    #[test]
+    fn test_timeline_unsupported() {
+        let xml = r#"<MPD mediaPresentationDuration="PT5S"><Period><AdaptationSet>
+          <Representation id="a" bandwidth="1"><BaseURL>a.mp4</BaseURL></Representation>
+          </AdaptationSet></Period></MPD>"#;
+        let mpd = parse(xml).unwrap();
+        let a = &mpd.periods[0].adaptations[0];
+        assert!(build(&a.representations[0], a, 0, &mpd).is_err());
+    }
+}
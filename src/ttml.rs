@@ -0,0 +1,418 @@
+//! Conversion of TTML subtitles to WebVTT.
+//!
+//! DASH streams often carry subtitles as IMSC1/TTML documents in fragmented MPEG-4 segments
+//! (`codecs="stpp"`), which few media players can read. These functions extract the TTML documents
+//! from the concatenated segments and convert their cues to WebVTT. Only the timing and text of
+//! each `<p>` element are converted (including line breaks); styling, regions and layout are
+//! dropped.
+
+
+use std::borrow::Cow;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use crate::DashMpdError;
+use crate::mp4::{boxes, read_u32, Mp4Box};
+
+
+/// A subtitle cue, with start and end times in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+
+fn mp4_error(why: &str) -> DashMpdError {
+    DashMpdError::Parsing(format!("parsing MP4 subtitle stream: {why}"))
+}
+
+// The sizes of the samples described by the TrackRunBoxes in a MovieFragmentBox, or None if the
+// sample sizes aren't specified.
+fn fragment_sample_sizes(moof: &[u8]) -> Result<Option<Vec<usize>>, DashMpdError> {
+    let mut sizes = Vec::new();
+    for traf in boxes(moof) {
+        let traf = traf.map_err(mp4_error)?;
+        if &traf.boxtype != b"traf" {
+            continue;
+        }
+        let mut default_size = None;
+        for b in boxes(traf.payload) {
+            let Mp4Box { boxtype, payload } = b.map_err(mp4_error)?;
+            let flags = read_u32(payload, 0).map_err(mp4_error)? & 0x00ff_ffff;
+            if &boxtype == b"tfhd" {
+                // skip the version/flags and track_ID fields, then the optional fields that precede
+                // default_sample_size
+                let mut pos = 8;
+                if flags & 0x01 != 0 { pos += 8; }
+                if flags & 0x02 != 0 { pos += 4; }
+                if flags & 0x08 != 0 { pos += 4; }
+                if flags & 0x10 != 0 {
+                    default_size = Some(read_u32(payload, pos).map_err(mp4_error)? as usize);
+                }
+            } else if &boxtype == b"trun" {
+                let count = read_u32(payload, 4).map_err(mp4_error)? as usize;
+                let mut pos = 8;
+                if flags & 0x01 != 0 { pos += 4; }
+                if flags & 0x04 != 0 { pos += 4; }
+                for _ in 0..count {
+                    if flags & 0x100 != 0 { pos += 4; }
+                    if flags & 0x200 != 0 {
+                        sizes.push(read_u32(payload, pos).map_err(mp4_error)? as usize);
+                        pos += 4;
+                    } else if let Some(size) = default_size {
+                        sizes.push(size);
+                    } else {
+                        return Ok(None);
+                    }
+                    if flags & 0x400 != 0 { pos += 4; }
+                    if flags & 0x800 != 0 { pos += 4; }
+                }
+            }
+        }
+    }
+    Ok(Some(sizes))
+}
+
+/// Extract the TTML documents carried as samples in an ISO-BMFF (fragmented MPEG-4) stream, such as
+/// the concatenated initialization and media segments of a DASH subtitle stream with codecs
+/// "stpp". The samples of each MediaDataBox are delimited using the sample sizes in the preceding
+/// MovieFragmentBox; if these aren't available, the MediaDataBox is assumed to contain a single
+/// sample.
+pub fn extract_ttml_samples(data: &[u8]) -> Result<Vec<String>, DashMpdError> {
+    let mut samples = Vec::new();
+    let mut sample_sizes = None;
+    for b in boxes(data) {
+        let Mp4Box { boxtype, payload } = b.map_err(mp4_error)?;
+        if &boxtype == b"moof" {
+            sample_sizes = fragment_sample_sizes(payload)?;
+        } else if &boxtype == b"mdat" {
+            let mut chunks = Vec::new();
+            match sample_sizes.take() {
+                Some(sizes) if sizes.iter().try_fold(0usize, |total, size| total.checked_add(*size))
+                    .is_some_and(|total| total <= payload.len()) => {
+                    let mut pos = 0;
+                    for size in sizes {
+                        chunks.push(&payload[pos..pos + size]);
+                        pos += size;
+                    }
+                },
+                _ => chunks.push(payload),
+            }
+            for chunk in chunks {
+                let ttml = String::from_utf8_lossy(chunk);
+                let ttml = ttml.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+                if !ttml.is_empty() {
+                    samples.push(ttml.to_string());
+                }
+            }
+        }
+    }
+    Ok(samples)
+}
+
+
+// Parse a TTML time expression, either a clock time (eg. "00:01:02.500" or "00:01:02:12" with a
+// frame count) or an offset time (eg. "62.5s", "1500ms", "90f", "450000t").
+fn parse_time_expression(s: &str, frame_rate: f64, tick_rate: f64) -> Option<f64> {
+    let s = s.trim();
+    if s.contains(':') {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 3 && parts.len() != 4 {
+            return None;
+        }
+        let hours: f64 = parts[0].parse().ok()?;
+        let minutes: f64 = parts[1].parse().ok()?;
+        let seconds: f64 = parts[2].parse().ok()?;
+        let frames: f64 = match parts.get(3) {
+            Some(f) => f.parse().ok()?,
+            None => 0.0,
+        };
+        Some(hours * 3600.0 + minutes * 60.0 + seconds + frames / frame_rate)
+    } else {
+        let metric_start = s.find(|c: char| c.is_ascii_alphabetic())?;
+        let (count, metric) = s.split_at(metric_start);
+        let count: f64 = count.parse().ok()?;
+        match metric {
+            "h" => Some(count * 3600.0),
+            "m" => Some(count * 60.0),
+            "s" => Some(count),
+            "ms" => Some(count / 1000.0),
+            "f" => Some(count / frame_rate),
+            "t" => Some(count / tick_rate),
+            _ => None,
+        }
+    }
+}
+
+fn attribute<'a>(e: &'a BytesStart, name: &str) -> Option<Cow<'a, str>> {
+    e.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key.local_name().as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok())
+}
+
+// The timing of an element in the document, in seconds from the start of the document timeline.
+#[derive(Clone, Copy)]
+struct Interval {
+    begin: f64,
+    end: Option<f64>,
+}
+
+/// Parse the cues of a TTML document: the `<p>` elements, with their timing resolved against the
+/// timing of their ancestor `<body>` and `<div>` elements. The text of nested `<span>`
+/// elements is included, `<br/>` elements become line breaks and whitespace is collapsed.
+/// Paragraphs without a resolvable end time are ignored.
+pub fn ttml_cues(ttml: &str) -> Result<Vec<Cue>, DashMpdError> {
+    let mut reader = Reader::from_str(ttml);
+    reader.expand_empty_elements(true);
+    let mut frame_rate = 30.0;
+    let mut tick_rate = 1.0;
+    let mut timing: Vec<Interval> = Vec::new();
+    let mut cues = Vec::new();
+    // the interval and text of the paragraph being parsed
+    let mut paragraph: Option<(Interval, String)> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = e.local_name();
+                let name = name.as_ref();
+                if name == b"tt" {
+                    if let Some(fr) = attribute(&e, "frameRate").and_then(|fr| fr.trim().parse::<f64>().ok()) {
+                        if fr > 0.0 {
+                            frame_rate = fr;
+                            tick_rate = fr;
+                        }
+                    }
+                    if let Some(tr) = attribute(&e, "tickRate").and_then(|tr| tr.trim().parse::<f64>().ok()) {
+                        if tr > 0.0 {
+                            tick_rate = tr;
+                        }
+                    }
+                }
+                if name == b"br" {
+                    if let Some((_, text)) = paragraph.as_mut() {
+                        text.push('\n');
+                    }
+                }
+                let parent = timing.last().copied().unwrap_or(Interval { begin: 0.0, end: None });
+                let time = |attr: &str| attribute(&e, attr)
+                    .and_then(|t| parse_time_expression(&t, frame_rate, tick_rate));
+                let begin = parent.begin + time("begin").unwrap_or(0.0);
+                let end = match (time("end"), time("dur")) {
+                    (Some(end), _) => Some(parent.begin + end),
+                    (None, Some(dur)) => Some(begin + dur),
+                    (None, None) => parent.end,
+                };
+                let interval = Interval { begin, end };
+                timing.push(interval);
+                if name == b"p" {
+                    paragraph = Some((interval, String::new()));
+                }
+            },
+            Ok(Event::Text(t)) => {
+                if let Some((_, text)) = paragraph.as_mut() {
+                    let t = t.unescape()
+                        .map_err(|e| DashMpdError::Parsing(format!("parsing TTML text: {e}")))?;
+                    // line breaks in the document source are whitespace; only <br/> breaks a line
+                    text.push_str(&t.replace(['\r', '\n'], " "));
+                }
+            },
+            Ok(Event::CData(t)) => {
+                if let Some((_, text)) = paragraph.as_mut() {
+                    text.push_str(&String::from_utf8_lossy(&t).replace(['\r', '\n'], " "));
+                }
+            },
+            Ok(Event::End(e)) => {
+                timing.pop();
+                if e.local_name().as_ref() == b"p" {
+                    if let Some((interval, text)) = paragraph.take() {
+                        let text = collapse_whitespace(&text);
+                        match interval.end {
+                            Some(end) if end > interval.begin && !text.is_empty() =>
+                                cues.push(Cue { start: interval.begin, end, text }),
+                            _ => (),
+                        }
+                    }
+                }
+            },
+            Ok(Event::Eof) => break,
+            Ok(_) => (),
+            Err(e) => return Err(DashMpdError::Parsing(format!("parsing TTML: {e}"))),
+        }
+    }
+    Ok(cues)
+}
+
+// Collapse runs of whitespace within each line to a single space, as for TTML's default
+// xml:space handling, and drop empty lines (which would terminate a WebVTT cue).
+fn collapse_whitespace(text: &str) -> String {
+    text.split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Sort the cues by start time, and merge cues with identical text that are repeated or continued
+// across segment boundaries (each segment of a DASH subtitle stream is a complete TTML document,
+// so a cue spanning two segments appears in both).
+fn merge_cues(mut cues: Vec<Cue>) -> Vec<Cue> {
+    cues.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
+    let mut merged: Vec<Cue> = Vec::new();
+    for cue in cues {
+        if let Some(prev) = merged.iter_mut().rev().find(|c| c.text == cue.text && cue.start <= c.end + 0.001) {
+            if cue.end > prev.end {
+                prev.end = cue.end;
+            }
+            continue;
+        }
+        merged.push(cue);
+    }
+    merged
+}
+
+fn vtt_timestamp(secs: f64) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}.{:03}",
+            millis / 3_600_000, (millis / 60_000) % 60, (millis / 1000) % 60, millis % 1000)
+}
+
+/// Format subtitle cues as a WebVTT document.
+pub fn webvtt(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for cue in cues {
+        let text = cue.text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        vtt += &format!("\n{} --> {}\n{text}\n", vtt_timestamp(cue.start), vtt_timestamp(cue.end));
+    }
+    vtt
+}
+
+/// Convert TTML subtitles to WebVTT. The input `data` is either a TTML document, or an ISO-BMFF
+/// stream of TTML samples (segmented IMSC1 subtitles with codecs "stpp").
+pub fn ttml_to_webvtt(data: &[u8]) -> Result<String, DashMpdError> {
+    let xml_start = data.iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map(|pos| data[pos..].starts_with(b"<") || data[pos..].starts_with(b"\xEF\xBB\xBF"))
+        .unwrap_or(false);
+    let documents = if xml_start {
+        vec![String::from_utf8_lossy(data).trim_start_matches('\u{feff}').to_string()]
+    } else {
+        extract_ttml_samples(data)?
+    };
+    let mut cues = Vec::new();
+    for doc in documents {
+        cues.extend(ttml_cues(&doc)?);
+    }
+    Ok(webvtt(&merge_cues(cues)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    // Build an ISO-BMFF box with the given type and payload.
+    fn mp4_box(boxtype: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(boxtype);
+        b.extend_from_slice(payload);
+        b
+    }
+
+    // A media segment in the form produced by common DASH packagers for IMSC1 subtitles: a moof
+    // with a trun giving the sample size, followed by an mdat containing one TTML document.
+    fn stpp_segment(ttml: &str) -> Vec<u8> {
+        let mut trun = vec![0, 0, 0x02, 0x01];  // version 0, data-offset-present and sample-size-present
+        trun.extend(1u32.to_be_bytes());
+        trun.extend(0u32.to_be_bytes());
+        trun.extend((ttml.len() as u32).to_be_bytes());
+        let mut tfhd = vec![0, 0x02, 0, 0];  // default-base-is-moof
+        tfhd.extend(1u32.to_be_bytes());
+        let traf = [mp4_box(b"tfhd", &tfhd), mp4_box(b"trun", &trun)].concat();
+        let moof = [mp4_box(b"mfhd", &[0, 0, 0, 0, 0, 0, 0, 1]), mp4_box(b"traf", &traf)].concat();
+        [mp4_box(b"styp", b"msdhmsdh"), mp4_box(b"moof", &moof), mp4_box(b"mdat", ttml.as_bytes())].concat()
+    }
+
+    const SEGMENT1: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling"
+    xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:tickRate="10000000" xml:lang="en">
+  <head><styling><style xml:id="s1" tts:color="white"/></styling></head>
+  <body style="s1">
+    <div>
+      <p begin="0t" end="20000000t">Hello,
+        <span tts:fontStyle="italic">world</span></p>
+      <p begin="30000000t" end="60000000t">A cue that spans<br/>two segments</p>
+    </div>
+  </body>
+</tt>"#;
+
+    const SEGMENT2: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter"
+    ttp:tickRate="10000000" xml:lang="en">
+  <body>
+    <div>
+      <p begin="60000000t" end="70000000t">A cue that spans<br/>two segments</p>
+      <p begin="80000000t" dur="15000000t">Fish &amp; chips &lt;3</p>
+    </div>
+  </body>
+</tt>"#;
+
+    #[test]
+    fn test_parse_time_expression() {
+        use super::parse_time_expression;
+
+        assert_eq!(parse_time_expression("00:01:02.500", 25.0, 1.0), Some(62.5));
+        assert_eq!(parse_time_expression("01:00:00:12", 25.0, 1.0), Some(3600.48));
+        assert_eq!(parse_time_expression("2.5s", 25.0, 1.0), Some(2.5));
+        assert_eq!(parse_time_expression("1500ms", 25.0, 1.0), Some(1.5));
+        assert_eq!(parse_time_expression("50f", 25.0, 1.0), Some(2.0));
+        assert_eq!(parse_time_expression("450000t", 25.0, 90000.0), Some(5.0));
+        assert_eq!(parse_time_expression("1m", 25.0, 1.0), Some(60.0));
+        assert_eq!(parse_time_expression("12", 25.0, 1.0), None);
+        assert_eq!(parse_time_expression("00:12", 25.0, 1.0), None);
+    }
+
+    #[test]
+    fn test_ttml_cues() {
+        use super::{ttml_cues, Cue};
+
+        let cues = ttml_cues(SEGMENT1).unwrap();
+        assert_eq!(cues, vec![
+            Cue { start: 0.0, end: 2.0, text: String::from("Hello, world") },
+            Cue { start: 3.0, end: 6.0, text: String::from("A cue that spans\ntwo segments") },
+        ]);
+        // timing is relative to the begin time of the enclosing div
+        let nested = r#"<tt xmlns="http://www.w3.org/ns/ttml"><body><div begin="10s">
+            <p begin="1s" end="2s">Later</p><p begin="3s">No end</p></div></body></tt>"#;
+        assert_eq!(ttml_cues(nested).unwrap(), vec![Cue { start: 11.0, end: 12.0, text: String::from("Later") }]);
+        assert!(ttml_cues("<tt><body><p begin=\"1s\" end=\"2s\">unclosed</body></tt>").is_err());
+    }
+
+    #[test]
+    fn test_stpp_to_webvtt() {
+        use super::{extract_ttml_samples, ttml_to_webvtt};
+
+        let mut init = mp4_box(b"ftyp", b"iso6dash");
+        init.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &[0; 100])));
+        let stream = [init, stpp_segment(SEGMENT1), stpp_segment(SEGMENT2)].concat();
+        let samples = extract_ttml_samples(&stream).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert!(samples[0].starts_with("<?xml"));
+        let vtt = ttml_to_webvtt(&stream).unwrap();
+        assert_eq!(vtt, "WEBVTT\n\n\
+                         00:00:00.000 --> 00:00:02.000\nHello, world\n\n\
+                         00:00:03.000 --> 00:00:07.000\nA cue that spans\ntwo segments\n\n\
+                         00:00:08.000 --> 00:00:09.500\nFish &amp; chips &lt;3\n");
+        // A plain TTML document is also accepted
+        assert!(ttml_to_webvtt(SEGMENT2.as_bytes()).unwrap().contains("00:00:06.000 --> 00:00:07.000"));
+        // Truncated boxes are reported as errors
+        assert!(extract_ttml_samples(&stream[..stream.len() - 10]).is_err());
+        // as are box sizes which overflow
+        let mut huge = vec![0, 0, 0, 1];
+        huge.extend_from_slice(b"mdat");
+        huge.extend(u64::MAX.to_be_bytes());
+        assert!(extract_ttml_samples(&[stream.as_slice(), &huge].concat()).is_err());
+    }
+}
@@ -21,7 +21,7 @@ fn test_dl1() {
     }
     let mpd_url = "https://cloudflarestream.com/31c9291ab41fac05471db4e73aa11717/manifest/video.mpd";
     let out = std::env::temp_dir().join("itec-elephants-dream.mp4");
-    assert!(DashDownloader::new(mpd_url)
+    assert!(DashDownloader::new(mpd_url).unwrap()
             .worst_quality()
             .download_to(out.clone()).is_ok());
     println!("DASH content saved to file {}", out.to_string_lossy());
@@ -48,7 +48,7 @@ fn test_downloader() {
     }
     fn check_mpd(mpd_url: &str, octets: u64, digest: &[u8]) {
         println!("Checking MPD URL {}", mpd_url);
-        match DashDownloader::new(mpd_url).download() {
+        match DashDownloader::new(mpd_url).unwrap().download() {
             Ok(path) => {
                 // check that ffprobe identifies this as a media file
                 let probed_meta = ffprobe(path.clone());
@@ -171,7 +171,7 @@ fn test_error_missing_mpd() {
         panic!("requesting DASH manifest");
     }
     let out = std::env::temp_dir().join("failure1.mkv");
-    DashDownloader::new("http://httpbin.org/status/404")
+    DashDownloader::new("http://httpbin.org/status/404").unwrap()
         .worst_quality()
         .download_to(out.clone()).unwrap();
 }
@@ -188,7 +188,7 @@ fn test_error_xlink_gone() {
         panic!("fetching XLink");
     }
     let out = std::env::temp_dir().join("failure1.mkv");
-    DashDownloader::new("https://dash.akamaized.net/dash264/TestCases/5c/nomor/5_1d.mpd")
+    DashDownloader::new("https://dash.akamaized.net/dash264/TestCases/5c/nomor/5_1d.mpd").unwrap()
         .worst_quality()
         .download_to(out.clone()).unwrap();
 }
@@ -0,0 +1,1877 @@
+// Tests for MPD download support using a local mock HTTP server
+//
+// These tests serve synthetic DASH manifests and fake media segments from a local HTTP server
+// (httpmock), so unlike the tests in fetching.rs they don't need external network access and are
+// run on CI infrastructure. The fake segments are not valid media, so we download only the video
+// stream (which doesn't require muxing) and check that the output is the concatenation of the
+// expected segments. The tests which check muxed output generate real media with ffmpeg, and are
+// skipped when ffmpeg isn't installed.
+
+
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use httpmock::prelude::*;
+use dash_mpd::DashMpdError;
+use dash_mpd::fetch::{DashDownloader, FailoverRange, FfmpegMuxer, MockClock, Muxer, MuxMeta, RetryErrorKind, RetryEvent, StreamSelector};
+use support::*;
+
+
+#[test]
+fn test_segment_list() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentList>
+          <Initialization sourceURL="v1/init.mp4"/>
+          <SegmentURL media="v1/seg1.mp4"/>
+          <SegmentURL media="v1/seg2.mp4"/>
+          <SegmentURL media="v1/seg3.mp4"/>
+        </SegmentList>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let (_, content) = download_video(&server, "segment-list").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+}
+
+#[test]
+fn test_segment_timeline() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" initialization="$RepresentationID$/init.mp4"
+                         media="$RepresentationID$/t$Time$.mp4">
+          <SegmentTimeline><S t="0" d="2000" r="1"/><S d="2000"/></SegmentTimeline>
+        </SegmentTemplate>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/t0.mp4", "T0;"),
+                                ("v1/t2000.mp4", "T2000;"), ("v1/t4000.mp4", "T4000;")]);
+    let (_, content) = download_video(&server, "segment-timeline").unwrap();
+    assert_eq!(content, "INIT;T0;T2000;T4000;");
+}
+
+#[test]
+fn test_segment_template_duration() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let (_, content) = download_video(&server, "segment-template").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+}
+
+// A segment whose body is shorter than its Content-Length is requested again. httpmock always sends
+// a correct Content-Length, so the segment is served by a minimal HTTP server which truncates its
+// first response (and closes the connection).
+// A WebM-DASH stream is muxed into a WebM container without re-encoding: the video stream in the
+// muxed output is identical to the video stream downloaded alone.
+#[test]
+fn test_webm_output() {
+    use ffprobe::ffprobe;
+
+    if !ffmpeg_available() {
+        eprintln!("Skipping test_webm_output: ffmpeg is not installed");
+        return;
+    }
+    let video = generate_media("vp9", "testsrc=size=320x240:rate=25", &["-c:v", "libvpx-vp9", "-f", "webm"]);
+    let audio = generate_media("opus", "sine=frequency=440", &["-c:a", "libopus", "-f", "webm"]);
+    let (Some(video), Some(audio)) = (video, audio) else {
+        eprintln!("Skipping test_webm_output: ffmpeg can't encode VP9 and Opus");
+        return;
+    };
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT2S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/webm" codecs="vp9">
+      <Representation id="v1" bandwidth="300000" width="320" height="240"><BaseURL>video.webm</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/webm" codecs="opus">
+      <Representation id="a1" bandwidth="64000"><BaseURL>audio.webm</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve_media(&server, manifest, &[("video.webm", "video/webm", video), ("audio.webm", "audio/webm", audio)]);
+    let streamhash = |path: &Path| {
+        let out = std::process::Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-map", "0:v", "-c", "copy", "-f", "streamhash", "-hash", "sha256", "-"])
+            .output()
+            .expect("running ffmpeg");
+        assert!(out.status.success());
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    let muxed = tmp_output("webm").with_extension("webm");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .record_metainformation(false)
+        .download_to(muxed.clone())
+        .unwrap();
+    let meta = ffprobe(muxed.clone()).expect("running ffprobe on muxed output");
+    let codecs: Vec<_> = meta.streams.iter().filter_map(|s| s.codec_name.as_deref()).collect();
+    assert!(codecs.contains(&"vp9") && codecs.contains(&"opus"), "{codecs:?}");
+    let video_only = tmp_output("webm-video").with_extension("webm");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .record_metainformation(false)
+        .download_to(video_only.clone())
+        .unwrap();
+    assert_eq!(streamhash(&muxed), streamhash(&video_only));
+    fs::remove_file(muxed).ok();
+    fs::remove_file(video_only).ok();
+}
+
+// The language of the selected audio AdaptationSet is recorded by the ffmpeg muxer in the muxed
+// output file, as an ISO 639-2 code.
+#[test]
+fn test_audio_language() {
+    use ffprobe::ffprobe;
+
+    if !ffmpeg_available() {
+        eprintln!("Skipping test_audio_language: ffmpeg is not installed");
+        return;
+    }
+    let video = generate_media("mpeg4", "testsrc=size=320x240:rate=25", &["-c:v", "mpeg4", "-f", "mp4"]);
+    let audio = generate_media("aac", "sine=frequency=440", &["-c:a", "aac", "-f", "mp4"]);
+    let (Some(video), Some(audio)) = (video, audio) else {
+        eprintln!("Skipping test_audio_language: ffmpeg can't encode MPEG-4 and AAC");
+        return;
+    };
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT2S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="300000" width="320" height="240"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="de">
+      <Representation id="de" bandwidth="64000"><BaseURL>audio-de.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="fr">
+      <Representation id="fr" bandwidth="64000"><BaseURL>audio-fr.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve_media(&server, manifest, &[("video.mp4", "video/mp4", video),
+                                     ("audio-de.mp4", "audio/mp4", audio.clone()),
+                                     ("audio-fr.mp4", "audio/mp4", audio)]);
+    for (container, lang, expected) in [("mp4", "de", "deu"), ("mkv", "fr", "fra")] {
+        let out = tmp_output(&format!("audio-language-{lang}")).with_extension(container);
+        DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+            .with_muxer(Box::new(FfmpegMuxer { location: String::from("ffmpeg") }))
+            .prefer_language(String::from(lang))
+            .record_metainformation(false)
+            .download_to(out.clone())
+            .unwrap();
+        let meta = ffprobe(out.clone()).expect("running ffprobe on muxed output");
+        let audio = meta.streams.iter()
+            .find(|s| s.codec_type.as_deref() == Some("audio"))
+            .expect("finding audio stream in muxed output");
+        let language = audio.tags.as_ref().and_then(|t| t.language.as_deref());
+        assert_eq!(language, Some(expected), "{container}");
+        fs::remove_file(out).ok();
+    }
+}
+
+#[test]
+fn test_truncated_segment() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let segment_url = format!("http://{}/v1/seg1.mp4", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&requests);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut count = counter.lock().unwrap();
+            *count += 1;
+            let body = if *count == 1 { "SE" } else { "SEG1;" };
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 5\r\nConnection: close\r\n\r\n{body}")
+                .unwrap();
+        }
+    });
+    let server = MockServer::start();
+    let manifest = video_manifest(&format!(r#"<SegmentList>
+          <Initialization sourceURL="v1/init.mp4"/>
+          <SegmentURL media="{segment_url}"/>
+          <SegmentURL media="v1/seg2.mp4"/>
+        </SegmentList>"#));
+    serve(&server, &manifest, &SEGMENTS);
+    let (report, content) = download_video(&server, "truncated").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;");
+    assert!(report.failed_video_segments.is_empty());
+    assert_eq!(*requests.lock().unwrap(), 2);
+}
+
+// Manifests with a non-conformant MPD@type such as "live" are downloaded as static manifests (with
+// a warning).
+#[test]
+fn test_unknown_mpd_type() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#)
+        .replace(r#"type="static""#, r#"type="live""#);
+    serve(&server, &manifest, &SEGMENTS);
+    let (_, content) = download_video(&server, "unknown-mpd-type").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+}
+
+// A Representation with more media segments than max_fragment_count is rejected before any
+// segment is requested.
+#[test]
+fn test_max_fragment_count() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let out = tmp_output("max-fragment-count");
+    let result = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .max_fragment_count(2)
+        .download_to(out.clone());
+    match result {
+        Err(DashMpdError::UnhandledMediaStream(msg)) =>
+            assert_eq!(msg, "Representation v1 has 3 media segments, more than the maximum of 2"),
+        other => panic!("expected an error, got {other:?}"),
+    }
+    assert!(!out.exists());
+}
+
+// If the index segment of SegmentTemplate@index addressing is unavailable, fall back to
+// SegmentTemplate@duration addressing.
+#[test]
+fn test_segment_template_index() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" index="$RepresentationID$/index.sidx"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let (_, content) = download_video(&server, "segment-template-index").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+}
+
+// The mock server doesn't honour HTTP Range requests, so the initialization byte range is sliced
+// locally from the full resource.
+#[test]
+fn test_segment_base() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<BaseURL>v1/media.mp4</BaseURL>
+        <SegmentBase indexRange="5-20">
+          <Initialization sourceURL="init.mp4" range="0-4"/>
+        </SegmentBase>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;IGNORED"), ("v1/media.mp4", "MEDIA;")]);
+    let (report, content) = download_video(&server, "segment-base").unwrap();
+    assert_eq!(content, "INIT;MEDIA;");
+    assert_eq!(report.range_support.values().collect::<Vec<_>>(), vec![&false]);
+}
+
+// Segments in the time ranges declared by a DVB-DASH FailoverContent element are not requested (the
+// mock server would return a 404 for them), and the ranges are listed in the DownloadReport.
+#[test]
+fn test_failover_content() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4">
+          <FailoverContent><FCS t="2000" d="2000"/></FailoverContent>
+        </SegmentTemplate>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/seg1.mp4", "SEG1;"), ("v1/seg3.mp4", "SEG3;")]);
+    let (report, content) = download_video(&server, "failover").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG3;");
+    assert_eq!(report.skipped_failover_ranges, vec![FailoverRange {
+        stream: String::from("video"),
+        period: 1,
+        start: Duration::from_secs(2),
+        duration: Duration::from_secs(2),
+    }]);
+
+    // An FCS without a @d attribute extends to the end of the Period.
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="10"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/t$Time$.mp4">
+          <SegmentTimeline><S t="0" d="20" r="2"/></SegmentTimeline>
+          <FailoverContent valid="false"><FCS t="20"/></FailoverContent>
+        </SegmentTemplate>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/t0.mp4", "T0;")]);
+    let (report, content) = download_video(&server, "failover-timeline").unwrap();
+    assert_eq!(content, "INIT;T0;");
+    assert_eq!(report.skipped_failover_ranges.len(), 1);
+    assert_eq!(report.skipped_failover_ranges[0].start, Duration::from_secs(2));
+    assert_eq!(report.skipped_failover_ranges[0].duration, Duration::from_secs(4));
+}
+
+// S@t defaults to 0 on the first S element of a SegmentTimeline, and to the end of the previous
+// segment on later elements; an explicit S@t sets the time of its first segment.
+#[test]
+fn test_segment_timeline_default_time() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="10"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/t$Time$.mp4">
+          <SegmentTimeline><S d="20" r="1"/><S d="10"/><S t="100" d="20"/></SegmentTimeline>
+        </SegmentTemplate>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/t0.mp4", "T0;"), ("v1/t20.mp4", "T20;"),
+                                ("v1/t40.mp4", "T40;"), ("v1/t100.mp4", "T100;")]);
+    let (_, content) = download_video(&server, "timeline-default-time").unwrap();
+    assert_eq!(content, "INIT;T0;T20;T40;T100;");
+}
+
+// A negative S@r repeats the segment until the start of the next S element, or until the end of the
+// Period. When the manifest doesn't specify a duration, the repetition is bounded by MPD@minBufferTime.
+#[test]
+fn test_segment_timeline_negative_repeat() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="10"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/t$Time$.mp4">
+          <SegmentTimeline><S t="0" d="10" r="-1"/><S t="30" d="30"/></SegmentTimeline>
+        </SegmentTemplate>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/t0.mp4", "T0;"), ("v1/t10.mp4", "T10;"),
+                                ("v1/t20.mp4", "T20;"), ("v1/t30.mp4", "T30;")]);
+    let (_, content) = download_video(&server, "timeline-repeat-next").unwrap();
+    assert_eq!(content, "INIT;T0;T10;T20;T30;");
+
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="10"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/t$Time$.mp4">
+          <SegmentTimeline><S t="0" d="20" r="-1"/></SegmentTimeline>
+        </SegmentTemplate>"#)
+        .replace(r#"mediaPresentationDuration="PT6S""#, r#"minBufferTime="PT6S""#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/t0.mp4", "T0;"), ("v1/t20.mp4", "T20;"),
+                                ("v1/t40.mp4", "T40;")]);
+    let (_, content) = download_video(&server, "timeline-repeat-no-duration").unwrap();
+    assert_eq!(content, "INIT;T0;T20;T40;");
+}
+
+// A long manifest: a SegmentTimeline with 50000 S elements (as in a 24-hour live archive). The full
+// list of segments is computed from the manifest, but only the first segment is downloaded.
+#[test]
+fn test_large_segment_timeline() {
+    let server = MockServer::start();
+    let timeline: String = (0..50_000)
+        .map(|i| format!(r#"<S t="{}" d="2000"/>"#, i * 2000))
+        .collect();
+    let manifest = video_manifest(&format!(r#"<SegmentTemplate timescale="1000"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/t$Time$.mp4">
+          <SegmentTimeline>{timeline}</SegmentTimeline>
+        </SegmentTemplate>"#))
+        .replace("PT6S", "PT27H47M");
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/t0.mp4", "T0;")]);
+    let out = tmp_output("large-timeline");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .segment_range(0, 1)
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "INIT;T0;");
+    fs::remove_file(out).ok();
+}
+
+// SegmentTemplate@media and @initialization may be absolute or protocol-relative URLs (for example
+// referencing another CDN), or relative URLs containing ../ segments.
+#[test]
+fn test_segment_template_absolute_urls() {
+    let server = MockServer::start();
+    let cdn = MockServer::start();
+    let cdn_url = cdn.url("/cdn");
+    let cdn_relative = cdn_url.trim_start_matches("http:");
+    let manifest = video_manifest(&format!(r#"<BaseURL>base/dir/</BaseURL>
+        <SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="{cdn_url}/$RepresentationID$/init.mp4"
+                         media="{cdn_relative}/$RepresentationID$/seg$Number$.mp4"/>"#));
+    serve(&server, &manifest, &[]);
+    serve(&cdn, "", &[("cdn/v1/init.mp4", "INIT;"), ("cdn/v1/seg1.mp4", "SEG1;"),
+                      ("cdn/v1/seg2.mp4", "SEG2;"), ("cdn/v1/seg3.mp4", "SEG3;")]);
+    let (_, content) = download_video(&server, "absolute-template").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<BaseURL>base/dir/</BaseURL>
+        <SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="../$RepresentationID$/init.mp4" media="../$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &[("base/v1/init.mp4", "INIT;"), ("base/v1/seg1.mp4", "SEG1;"),
+                                ("base/v1/seg2.mp4", "SEG2;"), ("base/v1/seg3.mp4", "SEG3;")]);
+    let (_, content) = download_video(&server, "dotdot-template").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+}
+
+#[test]
+fn test_base_url() {
+    let server = MockServer::start();
+    let manifest = video_manifest("<BaseURL>v1/media.mp4</BaseURL>");
+    serve(&server, &manifest, &[("v1/media.mp4", "MEDIA;")]);
+    let (_, content) = download_video(&server, "base-url").unwrap();
+    assert_eq!(content, "MEDIA;");
+}
+
+// A protocol-relative BaseURL, surrounded by whitespace from a pretty-printed manifest.
+#[test]
+fn test_base_url_protocol_relative() {
+    let server = MockServer::start();
+    let cdn = MockServer::start();
+    let cdn_relative = cdn.url("/content/").trim_start_matches("http:").to_string();
+    let manifest = video_manifest("<BaseURL>v1/media.mp4</BaseURL>")
+        .replace(r#"<Period id="1">"#,
+                 &format!("<Period id=\"1\">\n    <BaseURL>\n      {cdn_relative}\n    </BaseURL>"));
+    serve(&server, &manifest, &[]);
+    serve(&cdn, "", &[("content/v1/media.mp4", "MEDIA;")]);
+    let (_, content) = download_video(&server, "base-url-protocol-relative").unwrap();
+    assert_eq!(content, "MEDIA;");
+}
+
+// When the Representation doesn't specify its resolution, the @width and @height of the
+// AdaptationSet are used to describe it. @maxWidth and @maxHeight are only an upper bound, so they
+// aren't reported as the resolution.
+#[test]
+fn test_adaptation_resolution() {
+    let manifest = |attributes: &str| format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4" {attributes} par="16:9">
+      <SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                       initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="5070000" codecs="avc3.640028"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#);
+    let describe = |attributes: &str| {
+        let server = MockServer::start();
+        serve(&server, &manifest(attributes), &SEGMENTS);
+        let report = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+            .video_only()
+            .dry_run(true)
+            .download_to_with_report(tmp_output("adaptation-resolution"))
+            .unwrap();
+        report.dry_run.unwrap().representations
+    };
+    assert_eq!(describe(r#"width="1920" height="1080" maxWidth="3840" maxHeight="2160""#),
+               vec!["Period 1 video: id v1, bandwidth 5070000, codecs avc3.640028, 1920x1080"]);
+    assert_eq!(describe(r#"maxWidth="1920" maxHeight="1080""#),
+               vec!["Period 1 video: id v1, bandwidth 5070000, codecs avc3.640028"]);
+}
+
+#[test]
+fn test_xlink_period() {
+    let server = MockServer::start();
+    let manifest = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:xlink="http://www.w3.org/1999/xlink"
+     type="static" mediaPresentationDuration="PT6S">
+  <Period xlink:href="{}" xlink:actuate="onLoad"/>
+</MPD>"#, server.url("/period.xml"));
+    let period = r#"<Period id="remote">
+  <AdaptationSet contentType="video" mimeType="video/mp4">
+    <Representation id="v1" bandwidth="500000"><BaseURL>v1/media.mp4</BaseURL></Representation>
+  </AdaptationSet>
+</Period>"#;
+    server.mock(|when, then| {
+        when.path("/period.xml");
+        then.status(200).header("Content-Type", "application/xml").body(period);
+    });
+    serve(&server, &manifest, &[("v1/media.mp4", "REMOTE;")]);
+    let (_, content) = download_video(&server, "xlink").unwrap();
+    assert_eq!(content, "REMOTE;");
+}
+
+// The manifest lists two CDNs as alternative BaseURLs, and the first one fails to serve any media
+// segment. Each segment is then fetched from the second CDN, and the failing CDN isn't requested
+// again for the rest of the download, including in the second Period.
+#[test]
+fn test_cdn_failover() {
+    let server = MockServer::start();
+    let cdn_a = MockServer::start();
+    let cdn_b = MockServer::start();
+    let period = |id: &str| format!(r#"<Period id="{id}" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                       initialization="{id}/init.mp4" media="{id}/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>"#);
+    let manifest = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT8S">
+  <BaseURL serviceLocation="a">{}</BaseURL>
+  <BaseURL serviceLocation="b">{}</BaseURL>
+  {}
+  {}
+</MPD>"#, cdn_a.url("/content/"), cdn_b.url("/content/"), period("p1"), period("p2"));
+    serve(&server, &manifest, &[]);
+    let failing = cdn_a.mock(|when, then| {
+        when.path_contains("/content/");
+        then.status(503);
+    });
+    serve(&cdn_b, "", &[("content/p1/init.mp4", "P1INIT;"), ("content/p1/seg1.mp4", "P1S1;"), ("content/p1/seg2.mp4", "P1S2;"),
+                        ("content/p2/init.mp4", "P2INIT;"), ("content/p2/seg1.mp4", "P2S1;"), ("content/p2/seg2.mp4", "P2S2;")]);
+    let (_, content) = download_video(&server, "cdn-failover").unwrap();
+    assert_eq!(content, "P1INIT;P1S1;P1S2;P2INIT;P2S1;P2S2;");
+    failing.assert_hits(1);
+}
+
+// A BaseURL containing only whitespace is ignored in remote elements, as in the manifest itself.
+#[test]
+fn test_xlink_empty_base_url() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:xlink="http://www.w3.org/1999/xlink"
+     type="static" mediaPresentationDuration="PT6S">
+  <Period xlink:href="period.xml" xlink:actuate="onLoad"/>
+</MPD>"#;
+    let period = r#"<Period id="remote">
+  <BaseURL>
+  </BaseURL>
+  <BaseURL>remote/</BaseURL>
+  <AdaptationSet contentType="video" mimeType="video/mp4">
+    <Representation id="v1" bandwidth="500000"><BaseURL> </BaseURL><BaseURL>media.mp4</BaseURL></Representation>
+  </AdaptationSet>
+</Period>"#;
+    server.mock(|when, then| {
+        when.path("/period.xml");
+        then.status(200).header("Content-Type", "application/xml").body(period);
+    });
+    serve(&server, manifest, &[("remote/media.mp4", "REMOTE;")]);
+    let (_, content) = download_video(&server, "xlink-empty-base-url").unwrap();
+    assert_eq!(content, "REMOTE;");
+}
+
+// Remote SegmentLists, as used by some server-side ad insertion systems, on the audio AdaptationSet
+// and on the video Representation.
+#[test]
+fn test_xlink_segment_list() {
+    let server = MockServer::start();
+    let manifest = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:xlink="http://www.w3.org/1999/xlink"
+     type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000">
+        <SegmentList xlink:href="{}" xlink:actuate="onLoad"/>
+      </Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <SegmentList xlink:href="audio-segments.xml" xlink:actuate="onLoad"/>
+      <Representation id="a1" bandwidth="64000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#, server.url("/video-segments.xml"));
+    let video_segments = r#"<SegmentList>
+  <Initialization sourceURL="v1/init.mp4"/>
+  <SegmentURL media="v1/seg1.mp4"/>
+  <SegmentURL media="v1/seg2.mp4"/>
+</SegmentList>"#;
+    let audio_segments = r#"<SegmentList>
+  <SegmentURL media="a1/seg1.mp4"/>
+  <SegmentURL media="a1/seg2.mp4"/>
+</SegmentList>"#;
+    server.mock(|when, then| {
+        when.path("/video-segments.xml");
+        then.status(200).header("Content-Type", "application/xml").body(video_segments);
+    });
+    server.mock(|when, then| {
+        when.path("/audio-segments.xml");
+        then.status(200).header("Content-Type", "application/xml").body(audio_segments);
+    });
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;"), ("v1/seg1.mp4", "SEG1;"), ("v1/seg2.mp4", "SEG2;"),
+                                ("a1/seg1.mp4", "A1;"), ("a1/seg2.mp4", "A2;")]);
+    let (_, content) = download_video(&server, "xlink-segment-list").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;");
+
+    let out = tmp_output("xlink-segment-list-audio");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .audio_only()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "A1;A2;");
+    fs::remove_file(out).ok();
+}
+
+// A resolve-to-zero link removes the SegmentList, leaving plain BaseURL addressing.
+#[test]
+fn test_xlink_segment_list_resolve_to_zero() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<BaseURL>v1/media.mp4</BaseURL>
+        <SegmentList xmlns:xlink="http://www.w3.org/1999/xlink" xlink:href="urn:mpeg:dash:resolve-to-zero:2013"/>"#);
+    serve(&server, &manifest, &[("v1/media.mp4", "MEDIA;")]);
+    let (_, content) = download_video(&server, "xlink-segment-list-zero").unwrap();
+    assert_eq!(content, "MEDIA;");
+}
+
+#[test]
+fn test_multiperiod() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT8S">
+  <Period id="p1" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1" duration="2" startNumber="1"
+                       initialization="p1/init.mp4" media="p1/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+  <Period id="p2" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1" duration="2" startNumber="1"
+                       initialization="p2/init.mp4" media="p2/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("p1/init.mp4", "P1I;"), ("p1/seg1.mp4", "P1S1;"), ("p1/seg2.mp4", "P1S2;"),
+                               ("p2/init.mp4", "P2I;"), ("p2/seg1.mp4", "P2S1;"), ("p2/seg2.mp4", "P2S2;")]);
+    let (_, content) = download_video(&server, "multiperiod").unwrap();
+    assert_eq!(content, "P1I;P1S1;P1S2;P2I;P2S1;P2S2;");
+}
+
+#[test]
+fn test_error_manifest_404() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.path("/manifest.mpd");
+        then.status(404);
+    });
+    match download_video(&server, "manifest-404") {
+        Err(DashMpdError::Network(msg)) => assert!(msg.contains("requesting DASH manifest")),
+        other => panic!("expecting network error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_error_malformed_manifest() {
+    let server = MockServer::start();
+    serve(&server, r#"<?xml version="1.0"?><MPD><Period></PeriodZ></MPD>"#, &[]);
+    match download_video(&server, "malformed") {
+        Err(DashMpdError::Parsing(msg)) => assert!(msg.contains("parsing DASH XML")),
+        other => panic!("expecting parsing error, got {other:?}"),
+    }
+}
+
+// Conflicting stream options are reported before the manifest is fetched.
+#[test]
+fn test_error_audio_and_video_only() {
+    let server = MockServer::start();
+    let manifest = server.mock(|when, then| {
+        when.path("/manifest.mpd");
+        then.status(200).body(video_manifest(""));
+    });
+    let res = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .audio_only()
+        .video_only()
+        .download_to(tmp_output("audio-and-video-only"));
+    assert!(matches!(res, Err(DashMpdError::Config(_))));
+    manifest.assert_hits(0);
+}
+
+#[test]
+fn test_error_segment_404() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    // v1/seg2.mp4 is missing
+    serve(&server, &manifest, &[SEGMENTS[0], SEGMENTS[1], SEGMENTS[3]]);
+    match download_video(&server, "segment-404") {
+        Err(DashMpdError::Network(msg)) => assert!(msg.contains("seg2.mp4")),
+        other => panic!("expecting network error, got {other:?}"),
+    }
+    // With the default options, up to 10 failed media segments per stream are skipped and listed in
+    // the report.
+    let out = tmp_output("segment-404-tolerated");
+    let report = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .record_metainformation(false)
+        .download_to_with_report(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "INIT;SEG1;SEG3;");
+    assert_eq!(report.failed_video_segments, vec![server.url("/v1/seg2.mp4")]);
+    fs::remove_file(out).ok();
+}
+
+// With SegmentTemplate@duration addressing, the nominal duration of each stream is the number of
+// segments times the segment duration, so streams whose segments overrun the end of the Period by
+// different amounts are detected.
+#[test]
+fn test_strict_duration_match_template_duration() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT7S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" duration="3000" media="$RepresentationID$/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <SegmentTemplate timescale="1000" duration="2000" media="$RepresentationID$/seg$Number$.mp4"/>
+      <Representation id="a1" bandwidth="64000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[]);
+    let res = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .strict_duration_match()
+        .duration_tolerance(0.5)
+        .record_metainformation(false)
+        .download_to(tmp_output("strict-duration-template"));
+    match res {
+        Err(DashMpdError::UnhandledMediaStream(msg)) =>
+            assert!(msg.contains("audio stream has duration 8.000 seconds but video stream has duration 9.000 seconds"), "{msg}"),
+        other => panic!("expecting duration mismatch, got {other:?}"),
+    }
+}
+
+// Periods without a @duration last until the start of the next Period, and the last Period until
+// the end of the presentation, which determines the number of segments with SegmentTemplate@duration
+// addressing: 4 seconds (2 segments) and 6 seconds (3 segments).
+#[test]
+fn test_period_duration_from_start() {
+    let server = MockServer::start();
+    let period = |id: &str, start: &str| format!(r#"<Period id="{id}" start="{start}">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" duration="2000" startNumber="1" media="{id}/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>"#);
+    let manifest = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  {}
+  {}
+</MPD>"#, period("p1", "PT0S"), period("p2", "PT4S"));
+    serve(&server, &manifest, &[("p1/seg1.mp4", "P1S1;"), ("p1/seg2.mp4", "P1S2;"), ("p1/seg3.mp4", "P1S3;"),
+                                ("p2/seg1.mp4", "P2S1;"), ("p2/seg2.mp4", "P2S2;"), ("p2/seg3.mp4", "P2S3;")]);
+    let (_, content) = download_video(&server, "period-duration-from-start").unwrap();
+    assert_eq!(content, "P1S1;P1S2;P2S1;P2S2;P2S3;");
+}
+
+// timeline::build computes the same segments as those which are downloaded, in a multi-Period
+// manifest whose Periods have no @duration: the first Period (SegmentTemplate@duration) lasts until
+// the start of the second, whose SegmentTimeline S@r=-1 repeats until the end of the presentation.
+#[test]
+fn test_timeline_build_matches_download() {
+    use dash_mpd::timeline;
+
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  <Period id="p1" start="PT0S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" duration="2000" startNumber="1" media="p1/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+  <Period id="p2" start="PT6S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" media="p2/t$Time$.mp4">
+        <SegmentTimeline><S t="0" d="1500" r="-1"/></SegmentTimeline>
+      </SegmentTemplate>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[]);
+    server.mock(|when, then| {
+        when.path_contains("/p");
+        then.status(200)
+            .header("Content-Type", "video/mp4")
+            .body("SEGMENT;");
+    });
+    let fetched: Vec<_> = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .fetch_segments(StreamSelector::Video, None)
+        .unwrap()
+        .into_iter()
+        .map(|s| (s.url, s.start.unwrap().as_secs_f64()))
+        .collect();
+    let mpd = dash_mpd::parse(manifest).unwrap();
+    let mut built = Vec::new();
+    for (i, period) in mpd.periods.iter().enumerate() {
+        let adaptation = &period.adaptations[0];
+        for t in timeline::build(&adaptation.representations[0], adaptation, i, &mpd).unwrap() {
+            let path = if i == 0 { format!("/p1/seg{}.mp4", t.number) } else { format!("/p2/t{}.mp4", t.time) };
+            built.push((server.url(path), t.start_secs));
+        }
+    }
+    assert_eq!(fetched, built);
+    // 3 segments in the 6 seconds of the first Period, 3 in the 4 seconds of the second
+    assert_eq!(built.len(), 6);
+}
+
+// A missing initialization segment fails the download with the default options, and even when
+// media segments may be skipped, since the stream can't be decoded without it.
+#[test]
+fn test_error_init_segment_404() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS[1..]);
+    let out = tmp_output("init-404");
+    for max_errors in [None, Some(5)] {
+        let mut downloader = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+            .video_only()
+            .record_metainformation(false);
+        if let Some(count) = max_errors {
+            downloader = downloader.max_error_count_per_stream(count);
+        }
+        match downloader.download_to(out.clone()) {
+            Err(DashMpdError::Network(msg)) => {
+                assert!(msg.starts_with("failed to fetch the video initialization segment"), "{msg}");
+                assert!(msg.contains("init.mp4"));
+            },
+            other => panic!("expecting network error, got {other:?}"),
+        }
+    }
+    fs::remove_file(out).ok();
+}
+
+// A 503 response with a Retry-After header is a transient error: the request is retried after
+// the specified delay.
+#[test]
+fn test_error_segment_503_retried() {
+    let server = MockServer::start();
+    let manifest = video_manifest("<BaseURL>v1/media.mp4</BaseURL>");
+    serve(&server, &manifest, &[]);
+    let mut unavailable = server.mock(|when, then| {
+        when.path("/v1/media.mp4");
+        then.status(503).header("Retry-After", "1");
+    });
+    let content = thread::scope(|s| {
+        let download = s.spawn(|| download_video(&server, "segment-503"));
+        while unavailable.hits() < 1 && !download.is_finished() {
+            thread::sleep(Duration::from_millis(20));
+        }
+        unavailable.delete();
+        server.mock(|when, then| {
+            when.path("/v1/media.mp4");
+            then.status(200).header("Content-Type", "video/mp4").body("MEDIA;");
+        });
+        download.join().unwrap()
+    });
+    assert_eq!(content.unwrap().1, "MEDIA;");
+}
+
+// Each retry is reported to the on_retry hook, with the category of the error and the backoff delay.
+#[test]
+fn test_retry_events() {
+    let server = MockServer::start();
+    let manifest = video_manifest("<BaseURL>v1/media.mp4</BaseURL>");
+    serve(&server, &manifest, &[]);
+    let mut unavailable = server.mock(|when, then| {
+        when.path("/v1/media.mp4");
+        then.status(503).header("Retry-After", "1");
+    });
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&events);
+    let out = tmp_output("retry-events");
+    thread::scope(|s| {
+        let download = s.spawn(|| {
+            DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+                .video_only()
+                .record_metainformation(false)
+                .on_retry(Arc::new(move |ev| recorder.lock().unwrap().push(ev)))
+                .download_to(out.clone())
+        });
+        while unavailable.hits() < 1 && !download.is_finished() {
+            thread::sleep(Duration::from_millis(20));
+        }
+        unavailable.delete();
+        server.mock(|when, then| {
+            when.path("/v1/media.mp4");
+            then.status(200).header("Content-Type", "video/mp4").body("MEDIA;");
+        });
+        download.join().unwrap().unwrap();
+    });
+    fs::remove_file(out).ok();
+    let events = events.lock().unwrap();
+    assert_eq!(*events, vec![RetryEvent {
+        url: server.url("/v1/media.mp4"),
+        attempt: 1,
+        kind: RetryErrorKind::Status(503),
+        backoff: Duration::from_secs(1),
+    }]);
+}
+
+// A manifest with a <Location> element is fetched again from that URL (resolved against the URL of
+// the original manifest), and retries of that request are reported with the relocated URL.
+#[test]
+fn test_manifest_location() {
+    let relocated = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    let manifest = video_manifest("")
+        .replace("<Period", "<Location>live/relocated.mpd</Location>\n  <Period");
+    let server = MockServer::start();
+    serve(&server, &manifest, &[]);
+    server.mock(|when, then| {
+        when.path("/live/relocated.mpd");
+        then.status(200).header("Content-Type", "application/dash+xml").body(&relocated);
+    });
+    let segments: Vec<(String, &str)> = SEGMENTS.iter().map(|(p, c)| (format!("live/{p}"), *c)).collect();
+    for (path, content) in &segments {
+        let path = format!("/{path}");
+        server.mock(|when, then| {
+            when.path(path);
+            then.status(200).header("Content-Type", "video/mp4").body(content);
+        });
+    }
+    let (_, content) = download_video(&server, "location").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+
+    let server = MockServer::start();
+    serve(&server, &manifest, &[]);
+    server.mock(|when, then| {
+        when.path("/live/relocated.mpd");
+        then.status(503).header("Retry-After", "10");
+    });
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&events);
+    let result = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .record_metainformation(false)
+        .with_clock(Arc::new(MockClock::new(chrono::Utc::now())))
+        .on_retry(Arc::new(move |ev: RetryEvent| recorder.lock().unwrap().push(ev.url)))
+        .download_to(tmp_output("location-retry"));
+    assert!(matches!(result, Err(DashMpdError::Network(msg)) if msg.contains("relocated DASH manifest")));
+    let events = events.lock().unwrap();
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|url| *url == server.url("/live/relocated.mpd")), "{events:?}");
+}
+
+// With a mock clock, the delays requested by Retry-After headers are not waited for in real time,
+// and a request is abandoned once the retries have taken 15 minutes on the mock clock.
+#[test]
+fn test_mock_clock() {
+    let server = MockServer::start();
+    let manifest = video_manifest("<BaseURL>v1/media.mp4</BaseURL>");
+    serve(&server, &manifest, &[]);
+    let unavailable = server.mock(|when, then| {
+        when.path("/v1/media.mp4");
+        then.status(503).header("Retry-After", "120");
+    });
+    let clock = Arc::new(MockClock::new(chrono::Utc::now()));
+    let out = tmp_output("mock-clock");
+    let result = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .record_metainformation(false)
+        .with_clock(clock.clone())
+        .download_to(out.clone());
+    fs::remove_file(out).ok();
+    assert!(result.is_err());
+    assert_eq!(unavailable.hits(), 8);
+    assert_eq!(clock.sleeps(), vec![Duration::from_secs(120); 7]);
+}
+
+// A dynamic manifest is downloaded from the start of the time-shift buffer to the suggested
+// playback position, MPD@suggestedPresentationDelay behind the live edge.
+#[test]
+fn test_dynamic_suggested_delay() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="dynamic" availabilityStartTime="2024-01-01T00:00:00Z"
+     timeShiftBufferDepth="PT20S" suggestedPresentationDelay="PT10S" minimumUpdatePeriod="PT2S">
+  <Period id="1" start="PT0S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" duration="2000" initialization="$RepresentationID$/init.mp4"
+                       media="$RepresentationID$/seg$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    let mut segments = vec![(String::from("v1/init.mp4"), String::from("INIT;"))];
+    segments.extend((1..=30).map(|n| (format!("v1/seg{n}.mp4"), format!("S{n};"))));
+    let segments: Vec<(&str, &str)> = segments.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+    serve(&server, manifest, &segments);
+    // The live edge is 60 seconds after the availabilityStartTime, so the time-shift buffer starts
+    // at 40 seconds and the playback position is 50 seconds.
+    let start = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:01:00Z").unwrap()
+        .with_timezone(&chrono::Utc);
+    let out = tmp_output("dynamic");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .record_metainformation(false)
+        .with_clock(Arc::new(MockClock::new(start)))
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "INIT;S21;S22;S23;S24;S25;");
+    fs::remove_file(out).ok();
+}
+
+// AdaptationSets in different @group are complementary: by default only the first group is
+// downloaded, and download_complementary_groups also downloads one AdaptationSet of the other
+// group to a separate file.
+#[test]
+fn test_complementary_groups() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet group="1" contentType="video" mimeType="video/mp4">
+      <Representation id="main" bandwidth="500000"><BaseURL>main.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet group="1" contentType="video" mimeType="video/mp4">
+      <Representation id="alt" bandwidth="500000"><BaseURL>alt.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet group="2" contentType="video" mimeType="video/mp4">
+      <Role schemeIdUri="urn:mpeg:dash:role:2011" value="sign"/>
+      <Representation id="sign" bandwidth="200000"><BaseURL>sign.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("main.mp4", "MAIN;"), ("alt.mp4", "ALT;"), ("sign.mp4", "SIGN;")]);
+    let (report, content) = download_video(&server, "groups").unwrap();
+    assert_eq!(content, "MAIN;");
+    assert!(report.complementary_files.is_empty());
+
+    let out = tmp_output("groups-complementary");
+    let report = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .download_complementary_groups()
+        .record_metainformation(false)
+        .download_to_with_report(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "MAIN;");
+    assert_eq!(report.complementary_files, vec![out.with_extension("video-group2.mp4")]);
+    assert_eq!(fs::read_to_string(&report.complementary_files[0]).unwrap(), "SIGN;");
+    fs::remove_file(&report.complementary_files[0]).ok();
+    fs::remove_file(out).ok();
+}
+
+// Audio and video multiplexed in the same MPEG-2 TS segments, declared by ContentComponents or
+// implied by the absence of an audio AdaptationSet, are downloaded only once and not muxed.
+#[test]
+fn test_muxed_audio_video() {
+    for components in [r#"<ContentComponent id="1" contentType="video"/>
+      <ContentComponent id="2" contentType="audio" lang="en"/>"#, ""] {
+        let server = MockServer::start();
+        let manifest = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet mimeType="video/mp2t">
+      {components}
+      <SegmentList><SegmentURL media="ts1.ts"/><SegmentURL media="ts2.ts"/></SegmentList>
+      <Representation id="av" bandwidth="800000" codecs="avc1.4D401E,mp4a.40.2"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#);
+        serve(&server, &manifest, &[]);
+        let ts1 = server.mock(|when, then| {
+            when.path("/ts1.ts");
+            then.status(200).header("Content-Type", "video/mp2t").body("TS1;");
+        });
+        let ts2 = server.mock(|when, then| {
+            when.path("/ts2.ts");
+            then.status(200).header("Content-Type", "video/mp2t").body("TS2;");
+        });
+        let out = tmp_output("muxed");
+        let report = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+            .record_metainformation(false)
+            .download_to_with_report(out.clone())
+            .unwrap();
+        assert_eq!(fs::read_to_string(&report.output_path).unwrap(), "TS1;TS2;");
+        assert_eq!(ts1.hits(), 1);
+        assert_eq!(ts2.hits(), 1);
+        fs::remove_file(out).ok();
+    }
+}
+
+// Download metrics are POSTed to the reporting URL requested by a Metrics element in the manifest,
+// unless reporting is disabled.
+#[test]
+fn test_metrics_reporting() {
+    let server = MockServer::start();
+    let manifest = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:dvb="urn:dvb:dash:dash-extensions:2014-1"
+     type="static" mediaPresentationDuration="PT6S">
+  <Metrics metrics="DVBErrors">
+    <Reporting schemeIdUri="urn:dvb:dash:reporting:2014" value="1" dvb:reportingUrl="{}"/>
+  </Metrics>
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>v1/media.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#, server.url("/report"));
+    serve(&server, &manifest, &[("v1/media.mp4", "MEDIA;")]);
+    let report_url = server.url("/v1/media.mp4");
+    let reporting = server.mock(|when, then| {
+        when.method(POST)
+            .path("/report")
+            .header("Content-Type", "application/json")
+            .body_contains("\"metrics\":\"DVBErrors\"")
+            .body_contains(format!("\"url\":\"{report_url}\""))
+            .body_contains("\"octets\":6");
+        then.status(200);
+    });
+    let (report, _) = download_video(&server, "metrics").unwrap();
+    reporting.assert_hits(1);
+    assert_eq!(report.segment_metrics.len(), 1);
+    assert_eq!(report.segment_metrics[0].http_status, 200);
+
+    let out = tmp_output("metrics-disabled");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .disable_metrics_reporting(true)
+        .record_metainformation(false)
+        .download_to_with_report(out.clone())
+        .unwrap();
+    reporting.assert_hits(1);
+    fs::remove_file(out).ok();
+}
+
+// With bitstream switching, an initialization segment shared by successive Periods is downloaded
+// only once, whereas a per-Representation initialization segment is downloaded again when the
+// selected Representation changes.
+#[test]
+fn test_bitstream_switching() {
+    let manifest = |init: &str, p2_rid: &str| format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT8S">
+  <Period id="p1" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4" bitstreamSwitching="true">
+      <SegmentTemplate timescale="1" duration="2" startNumber="1"
+                       initialization="{init}" media="$RepresentationID$/p1-$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+  <Period id="p2" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4" bitstreamSwitching="true">
+      <SegmentTemplate timescale="1" duration="2" startNumber="1"
+                       initialization="{init}" media="$RepresentationID$/p2-$Number$.mp4"/>
+      <Representation id="{p2_rid}" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#);
+    let segments = [("init.mp4", "INIT;"), ("v1/init.mp4", "V1I;"), ("v2/init.mp4", "V2I;"),
+                    ("v1/p1-1.mp4", "P1S1;"), ("v1/p1-2.mp4", "P1S2;"),
+                    ("v1/p2-1.mp4", "P2S1;"), ("v1/p2-2.mp4", "P2S2;"),
+                    ("v2/p2-1.mp4", "V2S1;"), ("v2/p2-2.mp4", "V2S2;")];
+
+    let server = MockServer::start();
+    serve(&server, &manifest("init.mp4", "v1"), &segments);
+    let (_, content) = download_video(&server, "bss-shared").unwrap();
+    assert_eq!(content, "INIT;P1S1;P1S2;P2S1;P2S2;");
+
+    let server = MockServer::start();
+    serve(&server, &manifest("$RepresentationID$/init.mp4", "v1"), &segments);
+    let (_, content) = download_video(&server, "bss-same-rep").unwrap();
+    assert_eq!(content, "V1I;P1S1;P1S2;P2S1;P2S2;");
+
+    let server = MockServer::start();
+    serve(&server, &manifest("$RepresentationID$/init.mp4", "v2"), &segments);
+    let (_, content) = download_video(&server, "bss-rep-change").unwrap();
+    assert_eq!(content, "V1I;P1S1;P1S2;V2I;V2S1;V2S2;");
+
+    // A Period with another addressing mode comes between two Periods sharing an initialization
+    // segment, which must be downloaded again.
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  <Period id="p1" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4" bitstreamSwitching="true">
+      <SegmentTemplate timescale="1" duration="2" initialization="init.mp4" media="$RepresentationID$/p1-$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+  <Period id="ad" duration="PT2S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="ad" bandwidth="500000"><BaseURL>ad.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+  <Period id="p2" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4" bitstreamSwitching="true">
+      <SegmentTemplate timescale="1" duration="2" initialization="init.mp4" media="$RepresentationID$/p2-$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    let server = MockServer::start();
+    serve(&server, manifest, &[segments.as_slice(), &[("ad.mp4", "AD;")]].concat());
+    let (_, content) = download_video(&server, "bss-interrupted").unwrap();
+    assert_eq!(content, "INIT;P1S1;P1S2;AD;INIT;P2S1;P2S2;");
+}
+
+#[test]
+fn test_availability_time_incomplete() {
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT4S">
+  <Period id="p1" duration="PT4S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1" duration="2" startNumber="1" availabilityTimeComplete="false"
+                       initialization="init.mp4" media="seg-$Number$.mp4"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    let server = MockServer::start();
+    serve(&server, manifest, &[("init.mp4", "INIT;"), ("seg-1.mp4", "S1;"), ("seg-2.mp4", "S2;")]);
+    let (_, content) = download_video(&server, "incomplete").unwrap();
+    assert_eq!(content, "INIT;S1;S2;");
+}
+
+#[test]
+fn test_segment_template_inheritance() {
+    let manifest = |adaptation_start: &str| format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT4S">
+  <Period id="p1" duration="PT4S">
+    <SegmentTemplate timescale="1000" duration="2000" startNumber="5"
+                     initialization="init.mp4" media="seg-$Number$.mp4"/>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate {adaptation_start}/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#);
+    let segments = [("init.mp4", "INIT;"), ("seg-3.mp4", "S3;"), ("seg-4.mp4", "S4;"),
+                    ("seg-5.mp4", "S5;"), ("seg-6.mp4", "S6;")];
+
+    let server = MockServer::start();
+    serve(&server, &manifest(""), &segments);
+    let (_, content) = download_video(&server, "period-start").unwrap();
+    assert_eq!(content, "INIT;S5;S6;");
+
+    let server = MockServer::start();
+    serve(&server, &manifest(r#"startNumber="3""#), &segments);
+    let (_, content) = download_video(&server, "adaptation-start").unwrap();
+    assert_eq!(content, "INIT;S3;S4;");
+}
+
+#[test]
+fn test_fetch_segments() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                                         initialization="v1/init.mp4" media="v1/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let segments = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .fetch_segments(StreamSelector::Video, None)
+        .unwrap();
+    assert_eq!(segments.len(), 4);
+    assert!(segments[0].init);
+    assert_eq!(segments[0].bytes, b"INIT;");
+    assert!(segments[0].start.is_none());
+    for (i, segment) in segments[1..].iter().enumerate() {
+        assert!(!segment.init);
+        assert!(segment.url.ends_with(&format!("/v1/seg{}.mp4", i + 1)));
+        assert_eq!(segment.bytes, format!("SEG{};", i + 1).as_bytes());
+        assert_eq!(segment.start, Some(Duration::from_secs(2 * i as u64)));
+        assert_eq!(segment.duration, Some(Duration::from_secs(2)));
+    }
+
+    // The manifest has no audio stream
+    let segments = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .fetch_segments(StreamSelector::Audio, None)
+        .unwrap();
+    assert!(segments.is_empty());
+
+    // The limit on memory use is exceeded by the third segment
+    let result = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .fetch_segments(StreamSelector::Video, Some(12));
+    assert!(matches!(result, Err(DashMpdError::Other(_))));
+}
+
+// A version 0 Segment Index box with a reference to a media segment of each of `durations`.
+fn sidx_box(timescale: u32, durations: &[u32]) -> Vec<u8> {
+    let mut payload = vec![0, 0, 0, 0];
+    payload.extend(1u32.to_be_bytes());
+    payload.extend(timescale.to_be_bytes());
+    payload.extend(0u32.to_be_bytes());
+    payload.extend(0u32.to_be_bytes());
+    payload.extend((durations.len() as u32).to_be_bytes());
+    for d in durations {
+        payload.extend(100u32.to_be_bytes());
+        payload.extend(d.to_be_bytes());
+        payload.extend(0x9000_0000u32.to_be_bytes());
+    }
+    let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    b.extend_from_slice(b"sidx");
+    b.extend(payload);
+    b
+}
+
+// The number of media segments is determined by the Segment Index box in the index segment.
+#[test]
+fn test_segment_template_index_segment() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" index="$RepresentationID$/index.sidx" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let index = sidx_box(1000, &[4000, 2000]);
+    let index_mock = server.mock(|when, then| {
+        when.path("/v1/index.sidx");
+        then.status(200)
+            .header("Content-Type", "video/mp4")
+            .body(index);
+    });
+    let (_, content) = download_video(&server, "segment-template-index-segment").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;");
+    index_mock.assert();
+
+    let segments = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .fetch_segments(StreamSelector::Video, None)
+        .unwrap();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[2].start, Some(Duration::from_secs(4)));
+    assert_eq!(segments[2].duration, Some(Duration::from_secs(2)));
+}
+
+// Run the dash_mpd_cli example against the mock server. Cargo doesn't tell integration tests where
+// examples are built (there is no CARGO_BIN_EXE_ variable for them), and doesn't build them when a
+// single test target is selected, so this test is run explicitly with
+//    cargo build --examples && cargo test --test mock_server -- --ignored test_example_cli
+#[test]
+#[ignore = "requires the dash_mpd_cli example to be built with `cargo build --examples`"]
+fn test_example_cli() {
+    let exe = std::env::current_exe().unwrap();
+    let cli = exe.parent().and_then(|deps| deps.parent())
+        .map(|dir| dir.join("examples").join(format!("dash_mpd_cli{}", std::env::consts::EXE_SUFFIX)))
+        .unwrap();
+    assert!(cli.exists(), "dash_mpd_cli example not built at {}", cli.display());
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let out = tmp_output("example-cli");
+    let status = std::process::Command::new(&cli)
+        .args(["--video-only", "--no-progress", "--no-xattr", "--quality", "best", "-o"])
+        .arg(&out)
+        .arg(server.url("/manifest.mpd"))
+        .status()
+        .expect("running dash_mpd_cli");
+    assert!(status.success());
+    assert_eq!(fs::read_to_string(&out).unwrap(), "INIT;SEG1;SEG2;SEG3;");
+    fs::remove_file(out).ok();
+
+    let status = std::process::Command::new(&cli)
+        .args(["--no-progress", server.url("/missing.mpd").as_str()])
+        .status()
+        .expect("running dash_mpd_cli");
+    assert_eq!(status.code(), Some(2));
+}
+
+// Some encoders embed the initialization segment of the audio stream in a data URL with an
+// application/octet-stream MIME type.
+#[test]
+fn test_audio_data_url_octet_stream() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT4S">
+  <Period id="1">
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="en">
+      <Representation id="a1" bandwidth="64000" codecs="mp4a.40.2">
+        <SegmentList>
+          <Initialization sourceURL="data:application/octet-stream;base64,SU5JVDs="/>
+          <SegmentURL media="a1/seg1.mp4"/>
+          <SegmentURL media="a1/seg2.mp4"/>
+        </SegmentList>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("a1/seg1.mp4", "SEG1;"), ("a1/seg2.mp4", "SEG2;")]);
+    let out = tmp_output("audio-data-url");
+    let path = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .audio_only()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "INIT;SEG1;SEG2;");
+    fs::remove_file(out).ok();
+}
+
+// An AdaptationSet with an EssentialProperty whose scheme isn't understood (here a trick mode
+// AdaptationSet, for fast-forward playback) must be ignored, unless ignore_essential_properties is set.
+#[test]
+fn test_unknown_essential_property() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT4S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <EssentialProperty schemeIdUri="http://dashif.org/guidelines/trickmode" value="1"/>
+      <Representation id="trick" bandwidth="100000">
+        <SegmentList><SegmentURL media="trick/seg1.mp4"/></SegmentList>
+      </Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="full" bandwidth="500000">
+        <SegmentList><SegmentURL media="full/seg1.mp4"/></SegmentList>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("trick/seg1.mp4", "TRICK;"), ("full/seg1.mp4", "FULL;")]);
+    let (_, content) = download_video(&server, "essential-property").unwrap();
+    assert_eq!(content, "FULL;");
+
+    let out = tmp_output("essential-property-ignored");
+    let path = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .ignore_essential_properties()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "TRICK;");
+    fs::remove_file(out).ok();
+}
+
+// Segment requests carry the cookies set by the server in its response for the manifest, as well
+// as cookies supplied with with_cookie.
+#[test]
+fn test_cookies() {
+    use reqwest::cookie::CookieStore;
+
+    fn has_cookies(req: &httpmock::prelude::HttpMockRequest) -> bool {
+        let cookies: Vec<&str> = req.headers.iter().flatten()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+            .flat_map(|(_, value)| value.split("; "))
+            .collect();
+        cookies.contains(&"session=abc") && cookies.contains(&"auth=xyz")
+    }
+
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentList>
+          <Initialization sourceURL="v1/init.mp4"/>
+          <SegmentURL media="v1/seg1.mp4"/>
+        </SegmentList>"#);
+    server.mock(|when, then| {
+        when.path("/manifest.mpd");
+        then.status(200)
+            .header("Content-Type", "application/dash+xml")
+            .header("Set-Cookie", "session=abc; Path=/")
+            .body(manifest);
+    });
+    for (path, content) in [("/v1/init.mp4", "INIT;"), ("/v1/seg1.mp4", "SEG1;")] {
+        server.mock(|when, then| {
+            when.path(path).matches(has_cookies);
+            then.status(200)
+                .header("Content-Type", "video/mp4")
+                .body(content);
+        });
+    }
+    let out = tmp_output("cookies");
+    let dl = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .max_error_count_per_stream(0)
+        .record_metainformation(false)
+        .with_cookie("auth", "xyz", "127.0.0.1");
+    let jar = dl.cookie_jar();
+    let path = dl.download_to(out.clone()).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "INIT;SEG1;");
+    fs::remove_file(out).ok();
+    // The cookies are available for later requests
+    let url = reqwest::Url::parse(&server.url("/")).unwrap();
+    let cookies = jar.cookies(&url).unwrap();
+    assert!(cookies.to_str().unwrap().contains("session=abc"));
+}
+
+// A misconfigured server which compresses a media segment with Content-Encoding: gzip, though we
+// ask for the identity coding. The segment is decompressed once, whether or not the HTTP client was
+// built with gzip support.
+#[test]
+fn test_gzip_encoded_segment() {
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentList>
+          <Initialization sourceURL="v1/init.mp4"/>
+          <SegmentURL media="v1/seg1.mp4"/>
+        </SegmentList>"#);
+    serve(&server, &manifest, &[("v1/init.mp4", "INIT;")]);
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"SEG1;").unwrap();
+    let compressed = encoder.finish().unwrap();
+    let segment = server.mock(|when, then| {
+        when.path("/v1/seg1.mp4")
+            .header("Accept-Encoding", "identity");
+        then.status(200)
+            .header("Content-Type", "video/mp4")
+            .header("Content-Encoding", "gzip")
+            .body(compressed);
+    });
+    let (_, content) = download_video(&server, "gzip-segment").unwrap();
+    assert_eq!(content, "INIT;SEG1;");
+
+    let client = reqwest::blocking::Client::builder()
+        .gzip(false)
+        .build()
+        .unwrap();
+    let out = tmp_output("gzip-segment-client");
+    let path = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .with_http_client(client)
+        .video_only()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "INIT;SEG1;");
+    fs::remove_file(out).ok();
+    segment.assert_hits(2);
+}
+
+// When muxing fails, the downloaded streams are moved next to the output file and returned in the
+// error with the muxer's diagnostics, unless the user asks for them to be discarded. The streams
+// served here aren't valid media, so ffmpeg fails to mux them.
+#[test]
+fn test_mux_failure_preserves_streams() {
+    if !ffmpeg_available() {
+        return;
+    }
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <Representation id="a1" bandwidth="64000"><BaseURL>audio.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("video.mp4", "VIDEO;"), ("audio.mp4", "AUDIO;")]);
+    let out = tmp_output("mux-failure");
+    let ffmpeg = || Box::new(FfmpegMuxer { location: String::from("ffmpeg") });
+    let err = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .with_muxer(ffmpeg())
+        .without_content_type_checks()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap_err();
+    match err {
+        DashMpdError::MuxingFailed { message, audio: Some(audio), video: Some(video) } => {
+            // ffmpeg's diagnostics follow the prefix
+            let stderr = message.strip_prefix("running ffmpeg: ").unwrap();
+            assert!(!stderr.trim().is_empty(), "no ffmpeg stderr in {message:?}");
+            assert_eq!(audio, PathBuf::from(format!("{}.audio", out.display())));
+            assert_eq!(fs::read_to_string(&audio).unwrap(), "AUDIO;");
+            assert_eq!(fs::read_to_string(&video).unwrap(), "VIDEO;");
+            fs::remove_file(audio).ok();
+            fs::remove_file(video).ok();
+        },
+        e => panic!("unexpected error {e:?}"),
+    }
+
+    let err = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .with_muxer(ffmpeg())
+        .discard_streams_on_mux_failure()
+        .without_content_type_checks()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap_err();
+    assert!(matches!(err, DashMpdError::Muxing(msg) if msg.starts_with("running ffmpeg: ")));
+    assert!(!PathBuf::from(format!("{}.audio", out.display())).exists());
+}
+
+// Records the MuxMeta that it is called with.
+struct RecordingMuxer(Arc<Mutex<Option<MuxMeta>>>);
+
+impl Muxer for RecordingMuxer {
+    fn mux(&self, _audio: Option<&Path>, _video: Option<&Path>, out: &Path, meta: &MuxMeta) -> Result<(), DashMpdError> {
+        *self.0.lock().unwrap() = Some(meta.clone());
+        fs::write(out, "MUXED").map_err(|e| DashMpdError::io(e, "writing muxed output"))
+    }
+}
+
+// The language of the selected audio AdaptationSet and its Label (or Role) are passed to the muxer.
+#[test]
+fn test_mux_track_metadata() {
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="en">
+      <Role schemeIdUri="urn:mpeg:dash:role:2011" value="main"/>
+      <Representation id="a-en" bandwidth="64000"><BaseURL>audio-en.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="de-AT">
+      <Label>Deutsch (Österreich)</Label>
+      <Representation id="a-de" bandwidth="64000"><BaseURL>audio-de.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="fr">
+      <Role schemeIdUri="urn:mpeg:dash:role:2011" value="commentary"/>
+      <Representation id="a-fr" bandwidth="64000"><BaseURL>audio-fr.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("video.mp4", "VIDEO;"), ("audio-en.mp4", "EN;"),
+                               ("audio-de.mp4", "DE;"), ("audio-fr.mp4", "FR;")]);
+    let mux = |lang: &str| {
+        let recorded = Arc::new(Mutex::new(None));
+        let out = tmp_output(&format!("mux-metadata-{lang}"));
+        DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+            .prefer_language(String::from(lang))
+            .with_muxer(Box::new(RecordingMuxer(Arc::clone(&recorded))))
+            .without_content_type_checks()
+            .record_metainformation(false)
+            .download_to(out.clone())
+            .unwrap();
+        fs::remove_file(out).ok();
+        let meta = recorded.lock().unwrap().take().unwrap();
+        (meta.audio_language, meta.audio_title)
+    };
+    assert_eq!(mux("en"), (Some(String::from("en")), None));
+    assert_eq!(mux("de"), (Some(String::from("de-AT")), Some(String::from("Deutsch (Österreich)"))));
+    assert_eq!(mux("fr"), (Some(String::from("fr")), Some(String::from("commentary"))));
+}
+
+struct ConstantMuxer;
+
+impl Muxer for ConstantMuxer {
+    fn mux(&self, _audio: Option<&Path>, _video: Option<&Path>, out: &Path, _meta: &MuxMeta) -> Result<(), DashMpdError> {
+        fs::write(out, "MUXED").map_err(|e| DashMpdError::io(e, "writing muxed output"))
+    }
+}
+
+fn sha256_hex(data: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:x}", Sha256::digest(data.as_bytes()))
+}
+
+// The SHA-256 digests of the streams are computed as they are written, and the digest of the output
+// file is read back after muxing.
+#[test]
+fn test_checksums() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentList>
+          <Initialization sourceURL="v1/init.mp4"/>
+          <SegmentURL media="v1/seg1.mp4"/>
+          <SegmentURL media="v1/seg2.mp4"/>
+        </SegmentList>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let out = tmp_output("checksums");
+    let report = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .compute_checksums()
+        .record_metainformation(false)
+        .download_to_with_report(out.clone())
+        .unwrap();
+    let digest = sha256_hex("INIT;SEG1;SEG2;");
+    assert_eq!(report.video_sha256.as_ref(), Some(&digest));
+    assert_eq!(report.output_sha256.as_ref(), Some(&digest));
+    assert_eq!(report.audio_sha256, None);
+    fs::remove_file(&out).ok();
+
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <Representation id="a1" bandwidth="64000"><BaseURL>audio.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("video.mp4", "VIDEO;"), ("audio.mp4", "AUDIO;")]);
+    let report = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .with_muxer(Box::new(ConstantMuxer))
+        .without_content_type_checks()
+        .compute_checksums()
+        .record_metainformation(false)
+        .download_to_with_report(out.clone())
+        .unwrap();
+    assert_eq!(report.audio_sha256, Some(sha256_hex("AUDIO;")));
+    assert_eq!(report.video_sha256, Some(sha256_hex("VIDEO;")));
+    assert_eq!(report.output_sha256, Some(sha256_hex("MUXED")));
+    fs::remove_file(out).ok();
+}
+
+#[test]
+fn test_create_directories() {
+    let server = MockServer::start();
+    let manifest = video_manifest("<BaseURL>v1/media.mp4</BaseURL>");
+    serve(&server, &manifest, &[("v1/media.mp4", "MEDIA;")]);
+    let dir = std::env::temp_dir().join(format!("dashmpd-mock-dirs-{}", std::process::id()));
+    let out = dir.join("series").join("s01e01.mp4");
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .create_output_directories()
+        .record_metainformation(false)
+        .download_to(out.clone())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "MEDIA;");
+    fs::remove_dir_all(dir).ok();
+}
+
+// With strict_container_compatibility, a VP8 stream can't be downloaded to an MPEG-4 file, and the
+// error is reported before fetching any segments.
+#[test]
+fn test_error_container_compatibility() {
+    let server = MockServer::start();
+    let manifest = video_manifest("<BaseURL>v1/media.webm</BaseURL>")
+        .replace("video/mp4", "video/webm")
+        .replace("avc1.4D401E", "vp8");
+    serve(&server, &manifest, &[]);
+    let media = server.mock(|when, then| {
+        when.path("/v1/media.webm");
+        then.status(200).body("MEDIA;");
+    });
+    let res = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .strict_container_compatibility()
+        .download_to(tmp_output("container-compatibility"));
+    match res {
+        Err(DashMpdError::Config(msg)) => assert!(msg.contains("use a .webm output path"), "{msg}"),
+        other => panic!("expecting configuration error, got {other:?}"),
+    }
+    media.assert_hits(0);
+}
+
+// The fragment dump lists the fragments to be fetched, one JSON object per line, and is written
+// before any media segment is fetched (here for a dry run, which fetches no media).
+#[test]
+fn test_dump_fragments() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let dump = std::env::temp_dir().join(format!("dashmpd-mock-fragments-{}.jsonl", std::process::id()));
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .dry_run(true)
+        .dump_fragments(&dump)
+        .download_to(tmp_output("dump-fragments"))
+        .unwrap();
+    let records: Vec<serde_json::Value> = fs::read_to_string(&dump).unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    fs::remove_file(dump).ok();
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0]["stream"], "video");
+    assert_eq!(records[0]["init"], true);
+    assert!(records[0]["url"].as_str().unwrap().ends_with("/v1/init.mp4"));
+    for (i, r) in records.iter().enumerate() {
+        assert_eq!(r["index"], i);
+        assert_eq!(r["representation_id"], "v1");
+    }
+    assert_eq!(records[2]["init"], false);
+    assert!(records[2]["url"].as_str().unwrap().ends_with("/v1/seg2.mp4"));
+    assert_eq!(records[2]["start_secs"], 2.0);
+    assert_eq!(records[2]["duration_secs"], 2.0);
+    assert!(records[2]["start_byte"].is_null());
+}
+
+// A missing output directory is reported before the manifest is fetched.
+#[test]
+fn test_error_missing_output_directory() {
+    let server = MockServer::start();
+    let manifest = server.mock(|when, then| {
+        when.path("/manifest.mpd");
+        then.status(200).body(video_manifest(""));
+    });
+    let dir = std::env::temp_dir().join(format!("dashmpd-mock-nodir-{}", std::process::id()));
+    let res = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .download_to(dir.join("out.mp4"));
+    match res {
+        Err(DashMpdError::Io(_, msg)) => assert!(msg.contains("does not exist"), "{msg}"),
+        other => panic!("expecting I/O error, got {other:?}"),
+    }
+    manifest.assert_hits(0);
+    assert!(!dir.exists());
+}
+
+// At verbosity 0 nothing is printed on stdout or stderr, even when streams are kept and segments
+// fail. The test harness captures the output of tests in-process, so the download is run in a child
+// process (this test binary, running this test in child mode) whose output is captured between two
+// markers.
+#[test]
+fn test_quiet_at_verbosity_0() {
+    use std::io::Write;
+
+    const MARKER: &str = "DASHMPD-QUIET-MARKER";
+    if let Ok(url) = std::env::var("DASHMPD_QUIET_CHILD_URL") {
+        let out = tmp_output("quiet");
+        println!("{MARKER}");
+        eprintln!("{MARKER}");
+        std::io::stdout().flush().unwrap();
+        let res = DashDownloader::new(&url).unwrap()
+            .with_muxer(Box::new(ConstantMuxer))
+            .without_content_type_checks()
+            .keep_audio()
+            .keep_video()
+            .max_error_count_per_stream(1)
+            .record_metainformation(false)
+            .verbosity(0)
+            .download_to(out.clone());
+        std::io::stdout().flush().unwrap();
+        println!("{MARKER}");
+        eprintln!("{MARKER}");
+        res.unwrap();
+        fs::remove_file(out).ok();
+        return;
+    }
+    let server = MockServer::start();
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <Representation id="a1" bandwidth="64000">
+        <SegmentList><SegmentURL media="a1.mp4"/><SegmentURL media="missing.mp4"/></SegmentList>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    serve(&server, manifest, &[("video.mp4", "VIDEO;"), ("a1.mp4", "AUDIO;")]);
+    // The kept streams are written to the child's temporary directory, which we delete afterwards.
+    let tmpdir = std::env::temp_dir().join(format!("dashmpd-mock-quiet-{}", std::process::id()));
+    fs::create_dir_all(&tmpdir).unwrap();
+    let exe = std::env::current_exe().unwrap();
+    let output = std::process::Command::new(exe)
+        .args(["--exact", "test_quiet_at_verbosity_0", "--nocapture", "--test-threads=1"])
+        .env("DASHMPD_QUIET_CHILD_URL", server.url("/manifest.mpd"))
+        .env("TMPDIR", &tmpdir)
+        .output()
+        .expect("running child test process");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    for stream in [&output.stdout, &output.stderr] {
+        let text = String::from_utf8_lossy(stream);
+        let between: Vec<&str> = text.split(MARKER).collect();
+        assert_eq!(between.len(), 3, "{text}");
+        assert_eq!(between[1].trim(), "");
+    }
+    fs::remove_dir_all(tmpdir).ok();
+}
+
+// Write a fake ffprobe application which reports a duration of `duration` seconds into a fresh
+// directory, and return the location of a (nonexistent) ffmpeg application alongside it.
+#[cfg(unix)]
+fn fake_ffprobe(name: &str, duration: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("dashmpd-mock-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ffprobe = dir.join("ffprobe");
+    fs::write(&ffprobe, format!("#!/bin/sh\necho {duration}\n")).unwrap();
+    fs::set_permissions(&ffprobe, fs::Permissions::from_mode(0o755)).unwrap();
+    dir.join("ffmpeg")
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_output() {
+    let server = MockServer::start();
+    let manifest = video_manifest(r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+                         initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#);
+    serve(&server, &manifest, &SEGMENTS);
+    let download = |name: &str, duration: &str| {
+        let ffmpeg = fake_ffprobe(name, duration);
+        let out = tmp_output(name);
+        let result = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+            .video_only()
+            .record_metainformation(false)
+            .with_ffmpeg(ffmpeg.to_str().unwrap())
+            .verify_output(true)
+            .download_to(out.clone());
+        fs::remove_file(out).ok();
+        fs::remove_dir_all(ffmpeg.parent().unwrap()).ok();
+        result
+    };
+    assert!(download("verify-output-ok", "6.1").is_ok());
+    match download("verify-output-short", "2.0") {
+        Err(DashMpdError::Muxing(msg)) =>
+            assert_eq!(msg, "output file duration mismatch: expected 6.000s, got 2.000s"),
+        other => panic!("expected a duration mismatch, got {other:?}"),
+    }
+}
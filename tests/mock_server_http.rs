@@ -0,0 +1,244 @@
+// Tests of the HTTP behaviour of the downloader using a local mock HTTP server
+//
+// These tests use the httpmock fixtures in tests/support to exercise byte-range requests, retries
+// of failed and slow requests, and the error thresholds of the downloader. As in mock_server.rs, the
+// segments are not valid media, so we download only the video stream (which doesn't require
+// muxing) and check that the output is the concatenation of the expected segments.
+
+
+mod support;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use httpmock::prelude::*;
+use httpmock::Mock;
+use dash_mpd::DashMpdError;
+use dash_mpd::fetch::{DashDownloader, MockClock, ProgressObserver, RetryErrorKind};
+use support::*;
+
+
+fn downloader(server: &MockServer) -> DashDownloader {
+    DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .record_metainformation(false)
+        .with_clock(Arc::new(MockClock::new(chrono::Utc::now())))
+}
+
+const TEMPLATE: &str = r#"<SegmentTemplate timescale="1000" duration="2000" startNumber="1"
+    initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg$Number$.mp4"/>"#;
+
+
+// The initialization byte range of SegmentBase addressing is requested with a Range header, once a
+// probe of the first octet has shown that the server honours Range requests.
+#[test]
+fn test_segment_base_range() {
+    let server = MockServer::start();
+    let probe = server.mock(|when, then| {
+        when.path("/v1/init.mp4").header("Range", "bytes=0-0");
+        then.status(206)
+            .header("Content-Type", "video/mp4")
+            .header("Content-Range", "bytes 0-0/12")
+            .body("I");
+    });
+    let init = server.mock(|when, then| {
+        when.path("/v1/init.mp4").header("Range", "bytes=0-4");
+        then.status(206)
+            .header("Content-Type", "video/mp4")
+            .header("Content-Range", "bytes 0-4/12")
+            .body("INIT;");
+    });
+    let manifest = video_manifest(r#"<BaseURL>v1/media.mp4</BaseURL>
+        <SegmentBase><Initialization sourceURL="init.mp4" range="0-4"/></SegmentBase>"#);
+    serve(&server, &manifest, &[("v1/media.mp4", "MEDIA;")]);
+    let (_, content) = download(downloader(&server), "segment-base-range").unwrap();
+    assert_eq!(content, "INIT;MEDIA;");
+    assert_eq!(probe.hits(), 1);
+    assert_eq!(init.hits(), 1);
+}
+
+// SegmentList addressing with byte ranges of a single resource, served as 206 responses.
+#[test]
+fn test_segment_list_media_range() {
+    let server = MockServer::start();
+    let body = "INIT;SEG1;SEG2;TRAILER";
+    for (start, end) in [(0, 0), (0, 4), (5, 9), (10, 14)] {
+        server.mock(|when, then| {
+            when.path("/v1/media.mp4").header("Range", format!("bytes={start}-{end}"));
+            then.status(206)
+                .header("Content-Type", "video/mp4")
+                .header("Content-Range", format!("bytes {start}-{end}/{}", body.len()))
+                .body(&body[start..=end]);
+        });
+    }
+    let manifest = video_manifest(r#"<BaseURL>v1/media.mp4</BaseURL>
+        <SegmentList>
+          <Initialization range="0-4"/>
+          <SegmentURL mediaRange="5-9"/>
+          <SegmentURL mediaRange="10-14"/>
+        </SegmentList>"#);
+    serve(&server, &manifest, &[]);
+    let (report, content) = download(downloader(&server), "segment-list-range").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;");
+    assert!(report.range_support.values().all(|supported| *supported));
+}
+
+// Relative BaseURLs are resolved against the BaseURL of the enclosing MPD and Period, which differs
+// from one Period to the next.
+#[test]
+fn test_base_url_scoping_across_periods() {
+    let server = MockServer::start();
+    serve(&server, r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT4S">
+  <BaseURL>content/</BaseURL>
+  <Period id="1" duration="PT2S">
+    <BaseURL>first/</BaseURL>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+  <Period id="2" duration="PT2S">
+    <BaseURL>../second/</BaseURL>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"><BaseURL>video.mp4</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#, &[("content/first/video.mp4", "FIRST;"), ("second/video.mp4", "SECOND;")]);
+    let (_, content) = download(downloader(&server), "base-url-periods").unwrap();
+    assert_eq!(content, "FIRST;SECOND;");
+}
+
+// A segment which fails twice with 503 (Service Unavailable) is retried after the delay requested by
+// the Retry-After header, which with a mock clock doesn't slow down the test. The failing mock is
+// registered before the segments, so that it matches first, and is deleted at the second retry.
+#[test]
+fn test_retry_on_503() {
+    let server = Arc::new(MockServer::start());
+    let unavailable = server.mock(|when, then| {
+        when.path("/v1/seg2.mp4");
+        then.status(503).header("Retry-After", "30");
+    });
+    let unavailable_id = unavailable.id;
+    serve(&server, &video_manifest(TEMPLATE), &SEGMENTS);
+    let clock = Arc::new(MockClock::new(chrono::Utc::now()));
+    let mock_server = Arc::clone(&server);
+    let dl = downloader(&server)
+        .with_clock(clock.clone())
+        .on_retry(Arc::new(move |ev| if ev.attempt == 2 {
+            Mock::new(unavailable_id, &mock_server).delete();
+        }));
+    let (report, content) = download(dl, "retry-503").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+    assert!(report.failed_video_segments.is_empty());
+    assert_eq!(clock.sleeps(), vec![Duration::from_secs(30); 2]);
+}
+
+// A response slower than the timeout of the HTTP client is retried.
+#[test]
+fn test_retry_on_timeout() {
+    let server = Arc::new(MockServer::start());
+    let slow = server.mock(|when, then| {
+        when.path("/v1/seg1.mp4");
+        then.status(200)
+            .header("Content-Type", "video/mp4")
+            .body("SEG1;")
+            .delay(Duration::from_secs(2));
+    });
+    let slow_id = slow.id;
+    serve(&server, &video_manifest(TEMPLATE), &SEGMENTS);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&events);
+    let mock_server = Arc::clone(&server);
+    let dl = downloader(&server)
+        .with_http_client(client)
+        .on_retry(Arc::new(move |ev| {
+            recorder.lock().unwrap().push(ev.kind);
+            Mock::new(slow_id, &mock_server).delete();
+        }));
+    let (_, content) = download(dl, "retry-timeout").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG2;SEG3;");
+    assert_eq!(*events.lock().unwrap(), vec![RetryErrorKind::Timeout]);
+}
+
+// Serve /manifest.mpd with the SegmentTemplate segments, of which `missing` return a 404.
+fn serve_with_missing(server: &MockServer, missing: &[&str]) {
+    for path in missing {
+        server.mock(|when, then| {
+            when.path(format!("/{path}"));
+            then.status(404);
+        });
+    }
+    serve(server, &video_manifest(TEMPLATE), &SEGMENTS);
+}
+
+// Segments which fail permanently are skipped and reported, until more than max_error_count_per_stream
+// segments have failed.
+#[test]
+fn test_error_threshold() {
+    let server = MockServer::start();
+    serve_with_missing(&server, &["v1/seg1.mp4", "v1/seg3.mp4"]);
+    let (report, content) = download(downloader(&server).max_error_count_per_stream(2), "error-threshold").unwrap();
+    assert_eq!(content, "INIT;SEG2;");
+    assert_eq!(report.failed_video_segments, vec![server.url("/v1/seg1.mp4"), server.url("/v1/seg3.mp4")]);
+    assert!(download(downloader(&server).max_error_count_per_stream(1), "error-threshold-exceeded").is_err());
+}
+
+// Records the errors reported to a ProgressObserver.
+#[derive(Default)]
+struct ErrorRecorder {
+    errors: Mutex<Vec<String>>,
+}
+
+impl ProgressObserver for ErrorRecorder {
+    fn update(&self, _percent: u32, _message: &str) {}
+
+    fn on_error(&self, error: &DashMpdError) {
+        self.errors.lock().unwrap().push(error.to_string());
+    }
+}
+
+// Progress observers are told about each segment which fails to download, whether or not the
+// download continues, and about the error which aborts the download.
+#[test]
+fn test_progress_observer_errors() {
+    let server = MockServer::start();
+    serve_with_missing(&server, &["v1/seg2.mp4"]);
+    let segment_error = format!("network error fetching DASH video segment: HTTP status client error (404 Not Found) for url ({})",
+                                server.url("/v1/seg2.mp4"));
+
+    let recorder = Arc::new(ErrorRecorder::default());
+    let dl = downloader(&server)
+        .max_error_count_per_stream(1)
+        .add_progress_observer(recorder.clone());
+    let (_, content) = download(dl, "observer-errors").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG3;");
+    assert_eq!(*recorder.errors.lock().unwrap(), vec![segment_error.clone()]);
+
+    let recorder = Arc::new(ErrorRecorder::default());
+    let dl = downloader(&server)
+        .max_error_count_per_stream(0)
+        .add_progress_observer(recorder.clone());
+    let err = download(dl, "observer-errors-abort").unwrap_err();
+    assert_eq!(*recorder.errors.lock().unwrap(), vec![segment_error, err.to_string()]);
+}
+
+// A segment served with a Content-Type which isn't video (here an HTML error page from a
+// misconfigured CDN) is rejected, unless the content type checks are disabled.
+#[test]
+fn test_content_type_rejection() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.path("/v1/seg2.mp4");
+        then.status(200)
+            .header("Content-Type", "text/html")
+            .body("<html>");
+    });
+    serve(&server, &video_manifest(TEMPLATE), &SEGMENTS);
+    let (_, content) = download(downloader(&server), "content-type").unwrap();
+    assert_eq!(content, "INIT;SEG1;SEG3;");
+    let (_, content) = download(downloader(&server).without_content_type_checks(), "content-type-unchecked").unwrap();
+    assert_eq!(content, "INIT;SEG1;<html>SEG3;");
+}
@@ -29,3 +29,341 @@ fn test_mpd_parser () {
     // assert_matches!(parse(case3), Err(DashMpdError::Parsing));
 }
 
+
+#[test]
+fn test_suggested_delay () {
+    use std::time::Duration;
+    use dash_mpd::{parse, suggested_delay};
+
+    let live = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="dynamic" suggestedPresentationDelay="PT3.5S"
+     availabilityStartTime="2022-01-01T00:00:00Z" minimumUpdatePeriod="PT2S"><Period></Period></MPD>"#;
+    let mpd = parse(live).unwrap();
+    assert_eq!(mpd.suggestedPresentationDelay, Some(Duration::new(3, 500_000_000)));
+    assert_eq!(suggested_delay(&mpd), Some(Duration::new(3, 500_000_000)));
+
+    let live = r#"<?xml version="1.0" encoding="UTF-8"?><MPD type="dynamic"><Period></Period></MPD>"#;
+    assert_eq!(suggested_delay(&parse(live).unwrap()), None);
+
+    // The attribute is only meaningful for dynamic manifests
+    let vod = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD type="static" suggestedPresentationDelay="PT10S"><Period></Period></MPD>"#;
+    let mpd = parse(vod).unwrap();
+    assert_eq!(mpd.suggestedPresentationDelay, Some(Duration::new(10, 0)));
+    assert_eq!(suggested_delay(&mpd), None);
+}
+
+#[test]
+fn test_program_information () {
+    use dash_mpd::parse;
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  <ProgramInformation lang="fr" moreInformationURL="https://example.com/about">
+    <Title>Le titre</Title>
+    <Source>Example Productions</Source>
+    <Copyright>© 2023 Example</Copyright>
+  </ProgramInformation>
+  <Period></Period>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    let pi = mpd.ProgramInformation.unwrap();
+    assert_eq!(pi.title(), Some("Le titre"));
+    assert_eq!(pi.source(), Some("Example Productions"));
+    assert_eq!(pi.copyright(), Some("© 2023 Example"));
+    assert_eq!(pi.lang.as_deref(), Some("fr"));
+    assert_eq!(pi.moreInformationURL.as_deref(), Some("https://example.com/about"));
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><MPD><ProgramInformation/><Period></Period></MPD>"#;
+    let pi = parse(xml).unwrap().ProgramInformation.unwrap();
+    assert!(pi.title().is_none());
+    assert!(pi.moreInformationURL.is_none());
+}
+
+#[test]
+fn test_metrics () {
+    use std::time::Duration;
+    use dash_mpd::parse;
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:dvb="urn:dvb:dash:dash-extensions:2014-1" type="static">
+  <Metrics metrics="DVBErrors">
+    <Reporting schemeIdUri="urn:dvb:dash:reporting:2014" value="1"
+               dvb:reportingUrl="https://example.com/report" dvb:probability="1000" reportingInterval="10"/>
+    <Range starttime="PT0S" duration="PT30S"/>
+  </Metrics>
+  <Period></Period>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    assert_eq!(mpd.Metrics.len(), 1);
+    let m = &mpd.Metrics[0];
+    assert_eq!(m.metrics, "DVBErrors");
+    assert_eq!(m.reporting.len(), 1);
+    assert_eq!(m.reporting[0].schemeIdUri.as_deref(), Some("urn:dvb:dash:reporting:2014"));
+    assert_eq!(m.reporting[0].reportingUrl.as_deref(), Some("https://example.com/report"));
+    assert_eq!(m.reporting[0].probability, Some(1000));
+    assert_eq!(m.reporting[0].reportingInterval, Some(10));
+    assert_eq!(m.range.len(), 1);
+    assert_eq!(m.range[0].duration, Some(Duration::new(30, 0)));
+}
+
+#[test]
+fn test_availability_time_offset () {
+    use dash_mpd::parse;
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="dynamic" availabilityStartTime="2023-01-01T00:00:00Z">
+  <Period id="1" start="PT0S">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" duration="2000" availabilityTimeOffset="1.75" availabilityTimeComplete="false"
+                       initialization="init-$RepresentationID$.mp4" media="seg-$RepresentationID$-$Number$.m4s"/>
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    let st = mpd.periods[0].adaptations[0].SegmentTemplate.as_ref().unwrap();
+    assert_eq!(st.availabilityTimeOffset, Some(1.75));
+    assert_eq!(st.availabilityTimeComplete, Some(false));
+}
+
+#[test]
+fn test_audio_channel_configs() {
+    use dash_mpd::{parse, list_audio_channel_configs};
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  <Period>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000"/>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <AudioChannelConfiguration schemeIdUri="urn:mpeg:dash:23003:3:audio_channel_configuration:2011" value="2"/>
+      <Representation id="stereo" bandwidth="128000"/>
+      <Representation id="surround" bandwidth="384000">
+        <AudioChannelConfiguration schemeIdUri="urn:mpeg:mpegB:cicp:ChannelConfiguration" value="6"/>
+      </Representation>
+      <Representation id="unknown" bandwidth="384000">
+        <AudioChannelConfiguration schemeIdUri="urn:example:channels" value="6"/>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    assert_eq!(list_audio_channel_configs(&mpd),
+               vec![(2, String::from("stereo")), (6, String::from("surround"))]);
+}
+
+#[test]
+fn test_profiles() {
+    use dash_mpd::{parse, Profile};
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S"
+     profiles="urn:mpeg:dash:profile:isoff-live:2011, urn:dvb:dash:profile:dvb-dash:2014,urn:example:custom">
+  <Period id="1"/>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    assert_eq!(mpd.profiles(), vec![Profile::IsoffLive, Profile::DvbDash,
+                                    Profile::Other(String::from("urn:example:custom"))]);
+    assert!(!mpd.profiles().iter().any(Profile::is_mp2t));
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" profiles="urn:mpeg:dash:profile:mp2t-simple:2011">
+  <Period id="1"/>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    assert_eq!(mpd.profiles(), vec![Profile::Mp2tSimple]);
+    assert!(mpd.profiles()[0].is_mp2t());
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static"><Period id="1"/></MPD>"#;
+    assert!(parse(xml).unwrap().profiles().is_empty());
+}
+
+#[test]
+fn test_mpd_summary() {
+    use dash_mpd::parse;
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT1H">
+  <Period id="1">
+    <AdaptationSet contentType="video"/><AdaptationSet contentType="audio" lang="en"/>
+    <AdaptationSet contentType="audio" lang="fr"/><AdaptationSet contentType="text"/>
+  </Period>
+  <Period id="2">
+    <AdaptationSet contentType="video"/><AdaptationSet contentType="audio" lang="en"/>
+    <AdaptationSet contentType="audio" lang="fr"/><AdaptationSet contentType="text"/>
+  </Period>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    assert_eq!(mpd.summary(), "MPD[static, 2 periods, 3600s, 4 adaptations per period]");
+    let debug = format!("{mpd:?}");
+    assert!(debug.contains("periods: 2"));
+    assert!(!debug.contains("AdaptationSet"));
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="dynamic">
+  <Period id="1"><AdaptationSet contentType="video"/></Period>
+</MPD>"#;
+    assert_eq!(parse(xml).unwrap().summary(), "MPD[dynamic, 1 period, 1 adaptation per period]");
+}
+
+#[test]
+fn test_builders() {
+    use std::time::Duration;
+    use dash_mpd::{parse, SegmentTemplate};
+    use dash_mpd::builder::{MpdBuilder, PeriodBuilder, AdaptationSetBuilder, RepresentationBuilder};
+
+    let template = SegmentTemplate {
+        timescale: Some(1000),
+        duration: Some(2000.0),
+        media: Some(String::from("$RepresentationID$/$Number$.m4s")),
+        ..Default::default()
+    };
+    let mpd = MpdBuilder::new()
+        .media_presentation_duration(Duration::from_secs(6))
+        .base_url("https://example.com/media/")
+        .period(PeriodBuilder::new()
+                .id("p1")
+                .adaptation(AdaptationSetBuilder::new()
+                            .content_type("video")
+                            .mime_type("video/mp4")
+                            .max_resolution(1920, 1080)
+                            .segment_template(template)
+                            .representation(RepresentationBuilder::new("v1")
+                                            .codecs("avc1.640028")
+                                            .bandwidth(5_000_000)
+                                            .resolution(1920, 1080)
+                                            .build())
+                            .build())
+                .adaptation(AdaptationSetBuilder::new()
+                            .content_type("audio")
+                            .lang("fr")
+                            .representation(RepresentationBuilder::new("a1")
+                                            .audio_channel_configuration(
+                                                "urn:mpeg:dash:23003:3:audio_channel_configuration:2011", "2")
+                                            .base_url("audio.mp4")
+                                            .build())
+                            .build())
+                .build())
+        .build();
+    assert_eq!(mpd.summary(), "MPD[static, 1 period, 6s, 2 adaptations per period]");
+    // The manifest survives a round trip through XML.
+    let xml = quick_xml::se::to_string(&mpd).unwrap();
+    let mpd = parse(&xml).unwrap();
+    let video = &mpd.periods[0].adaptations[0];
+    assert_eq!(video.maxWidth, Some(1920));
+    assert_eq!(video.representations[0].bandwidth, Some(5_000_000));
+    assert_eq!(video.SegmentTemplate.as_ref().unwrap().timescale, Some(1000));
+    let audio = &mpd.periods[0].adaptations[1];
+    assert_eq!(audio.lang.as_deref(), Some("fr"));
+    assert_eq!(audio.representations[0].BaseURL[0].base, "audio.mp4");
+}
+
+#[test]
+fn test_field_name_aliases() {
+    use dash_mpd::parse;
+
+    // Elements named as in the DASH specification.
+    let spec = r#"<MPD type="static" mediaPresentationDuration="PT6S">
+      <Location>https://example.com/new.mpd</Location>
+      <Period>
+        <BaseURL>https://cdn.example.com/</BaseURL>
+        <AdaptationSet contentType="video">
+          <Representation id="v1" bandwidth="100000"/>
+        </AdaptationSet>
+      </Period>
+    </MPD>"#;
+    // The same manifest using the names of the Rust struct fields.
+    let rusty = r#"<MPD mpdtype="static" mediaPresentationDuration="PT6S">
+      <locations>https://example.com/new.mpd</locations>
+      <periods>
+        <base_url>https://cdn.example.com/</base_url>
+        <adaptations contentType="video">
+          <representations id="v1" bandwidth="100000"/>
+        </adaptations>
+      </periods>
+    </MPD>"#;
+    for xml in [spec, rusty] {
+        let mpd = parse(xml).unwrap();
+        assert_eq!(mpd.mpdtype.as_deref(), Some("static"));
+        assert_eq!(mpd.locations[0].url, "https://example.com/new.mpd");
+        let period = &mpd.periods[0];
+        assert_eq!(period.BaseURL[0].base, "https://cdn.example.com/");
+        assert_eq!(period.adaptations[0].representations[0].bandwidth, Some(100_000));
+    }
+}
+
+#[test]
+fn test_content_protection_license_url() {
+    use dash_mpd::parse;
+
+    // The mspr:pro element holds a PlayReady Object containing a single PlayReady Header record
+    // (UTF-16LE XML) with the KID and license server URL of the Axinom test streams. It was encoded
+    // separately from this crate, following the PlayReady Header Specification.
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:cenc="urn:mpeg:cenc:2013"
+     xmlns:mspr="urn:microsoft:playready" xmlns:dashif="https://dashif.org/CPS"
+     xmlns:clearkey="http://dashif.org/guidelines/clearKey" type="static">
+  <Period id="1">
+    <AdaptationSet contentType="video">
+      <ContentProtection schemeIdUri="urn:mpeg:dash:mp4protection:2011" value="cenc"
+                         cenc:default_KID="9eb4050d-e44b-4802-932e-27d75083e266"/>
+      <ContentProtection schemeIdUri="urn:uuid:9a04f079-9840-4286-ab92-e65be0885f95" value="MSPR 2.0">
+        <mspr:pro>
+          WAIAAAEAAQBOAjwAVwBSAE0ASABFAEEARABFAFIAIAB4AG0AbABuAHMAPQAiAGgAdAB0AHAAOgAvAC8AcwBjAGgA
+          ZQBtAGEAcwAuAG0AaQBjAHIAbwBzAG8AZgB0AC4AYwBvAG0ALwBEAFIATQAvADIAMAAwADcALwAwADMALwBQAGwA
+          YQB5AFIAZQBhAGQAeQBIAGUAYQBkAGUAcgAiACAAdgBlAHIAcwBpAG8AbgA9ACIANAAuADAALgAwAC4AMAAiAD4A
+          PABEAEEAVABBAD4APABQAFIATwBUAEUAQwBUAEkATgBGAE8APgA8AEsARQBZAEwARQBOAD4AMQA2ADwALwBLAEUA
+          WQBMAEUATgA+ADwAQQBMAEcASQBEAD4AQQBFAFMAQwBUAFIAPAAvAEEATABHAEkARAA+ADwALwBQAFIATwBUAEUA
+          QwBUAEkATgBGAE8APgA8AEsASQBEAD4ARABRAFcAMABuAGsAdgBrAEEAawBpAFQATABpAGYAWABVAEkAUABpAFoA
+          ZwA9AD0APAAvAEsASQBEAD4APABMAEEAXwBVAFIATAA+AGgAdAB0AHAAcwA6AC8ALwBkAHIAbQAtAHAAbABhAHkA
+          cgBlAGEAZAB5AC0AbABpAGMAZQBuAHMAaQBuAGcALgBhAHgAdABlAHMAdAAuAG4AZQB0AC8AQQBjAHEAdQBpAHIA
+          ZQBMAGkAYwBlAG4AcwBlADwALwBMAEEAXwBVAFIATAA+ADwALwBEAEEAVABBAD4APAAvAFcAUgBNAEgARQBBAEQA
+          RQBSAD4A
+        </mspr:pro>
+      </ContentProtection>
+      <ContentProtection schemeIdUri="urn:uuid:edef8ba9-79d6-4ace-a3c8-27dcd51d21ed" value="Widevine">
+        <dashif:laurl>https://drm-widevine-licensing.axtest.net/AcquireLicense</dashif:laurl>
+      </ContentProtection>
+      <ContentProtection schemeIdUri="urn:uuid:e2719d58-a985-b3c9-781a-b030af78d30e" value="ClearKey1.0">
+        <clearkey:Laurl Lic_type="EME-1.0">https://dash.akamaized.net/dash.js/clearkey/license</clearkey:Laurl>
+      </ContentProtection>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+    let mpd = parse(xml).unwrap();
+    let cp = &mpd.periods[0].adaptations[0].ContentProtection;
+    assert_eq!(cp.len(), 4);
+    assert_eq!(cp[0].license_url(), None);
+    assert_eq!(cp[1].license_url().as_deref(),
+               Some("https://drm-playready-licensing.axtest.net/AcquireLicense"));
+    assert_eq!(cp[2].license_url().as_deref(),
+               Some("https://drm-widevine-licensing.axtest.net/AcquireLicense"));
+    assert_eq!(cp[3].clearkey_laurl.as_ref().unwrap().licenseType.as_deref(), Some("EME-1.0"));
+    assert_eq!(cp[3].license_url().as_deref(),
+               Some("https://dash.akamaized.net/dash.js/clearkey/license"));
+}
+
+// An IMSC1 subtitle stream (codecs "stpp") in fragmented MPEG-4: an init segment and three media
+// segments (each with styp and sidx boxes) in the layouts written by common packagers. The sample
+// sizes are given by tfhd@default_sample_size in the first segment and by the trun in the second
+// (whose mdat holds two TTML documents), and are absent in the third, whose mdat holds a single,
+// padded document.
+#[test]
+fn test_stpp_fixture_to_webvtt() {
+    use dash_mpd::ttml::{extract_ttml_samples, ttml_to_webvtt};
+
+    let stream = include_bytes!("fixtures/stpp-imsc1.mp4");
+    let samples = extract_ttml_samples(stream).unwrap();
+    assert_eq!(samples.len(), 4);
+    assert!(samples.iter().all(|s| s.starts_with("<?xml") && s.ends_with("</tt>")));
+    assert_eq!(ttml_to_webvtt(stream).unwrap(),
+               "WEBVTT\n\n\
+                00:00:00.000 --> 00:00:02.000\nHello,\nworld\n\n\
+                00:00:03.000 --> 00:00:04.000\nA cue that spans\n\n\
+                00:00:04.000 --> 00:00:05.000\ntwo segments\n\n\
+                00:00:06.000 --> 00:00:07.000\nFish &amp; chips &lt;3\n\n\
+                00:00:08.500 --> 00:00:09.500\nThe end\n");
+}
@@ -0,0 +1,111 @@
+// Fixtures shared by the integration tests which download from a local mock HTTP server (httpmock):
+// serving synthetic manifests and fake media segments, and downloading the video stream.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use httpmock::prelude::*;
+use dash_mpd::DashMpdError;
+use dash_mpd::fetch::{DashDownloader, DownloadReport};
+
+
+// Serve the manifest at /manifest.mpd and each of the media segments at its path.
+pub fn serve(server: &MockServer, manifest: &str, segments: &[(&str, &str)]) {
+    let manifest = manifest.to_string();
+    server.mock(|when, then| {
+        when.path("/manifest.mpd");
+        then.status(200)
+            .header("Content-Type", "application/dash+xml")
+            .body(manifest);
+    });
+    for (path, content) in segments {
+        let path = format!("/{path}");
+        server.mock(|when, then| {
+            when.path(path);
+            then.status(200)
+                .header("Content-Type", "video/mp4")
+                .body(content);
+        });
+    }
+}
+
+pub fn tmp_output(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dashmpd-mock-{name}-{}.mp4", std::process::id()))
+}
+
+// Download with `dl` to a temporary file, returning the download report and the downloaded content.
+pub fn download(dl: DashDownloader, name: &str) -> Result<(DownloadReport, String), DashMpdError> {
+    let out = tmp_output(name);
+    let report = dl.download_to_with_report(out.clone())?;
+    let content = fs::read_to_string(&report.output_path)
+        .expect("reading downloaded content");
+    fs::remove_file(out).ok();
+    Ok((report, content))
+}
+
+// Download the video stream of /manifest.mpd, failing on the first segment error.
+pub fn download_video(server: &MockServer, name: &str) -> Result<(DownloadReport, String), DashMpdError> {
+    let dl = DashDownloader::new(&server.url("/manifest.mpd")).unwrap()
+        .video_only()
+        .max_error_count_per_stream(0)
+        .record_metainformation(false);
+    download(dl, name)
+}
+
+// A static manifest with a single video AdaptationSet, whose Representation contains `addressing`.
+pub fn video_manifest(addressing: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT6S">
+  <Period id="1">
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="500000" width="640" height="360" codecs="avc1.4D401E">
+        {addressing}
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#)
+}
+
+pub const SEGMENTS: [(&str, &str); 4] = [
+    ("v1/init.mp4", "INIT;"),
+    ("v1/seg1.mp4", "SEG1;"),
+    ("v1/seg2.mp4", "SEG2;"),
+    ("v1/seg3.mp4", "SEG3;")];
+
+// Whether the ffmpeg and ffprobe applications are installed, for the tests which mux real media.
+pub fn ffmpeg_available() -> bool {
+    ["ffmpeg", "ffprobe"].iter().all(|app| {
+        std::process::Command::new(app).arg("-version").output()
+            .is_ok_and(|out| out.status.success())
+    })
+}
+
+// Generate two seconds of synthetic media from the ffmpeg lavfi `source`, encoded with the output
+// options `args`. Returns None if ffmpeg fails (for example when the encoder isn't available).
+pub fn generate_media(name: &str, source: &str, args: &[&str]) -> Option<Vec<u8>> {
+    let path = std::env::temp_dir().join(format!("dashmpd-mock-media-{name}-{}", std::process::id()));
+    let out = std::process::Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "lavfi", "-i", source, "-t", "2"])
+        .args(args)
+        .arg(&path)
+        .output()
+        .ok()?;
+    let media = out.status.success().then(|| fs::read(&path).ok()).flatten();
+    fs::remove_file(&path).ok();
+    media
+}
+
+// Serve the manifest at /manifest.mpd and each of the binary media files at its path.
+pub fn serve_media(server: &MockServer, manifest: &str, media: &[(&str, &str, Vec<u8>)]) {
+    serve(server, manifest, &[]);
+    for (path, content_type, content) in media {
+        let path = format!("/{path}");
+        server.mock(|when, then| {
+            when.path(path);
+            then.status(200)
+                .header("Content-Type", *content_type)
+                .body(content);
+        });
+    }
+}